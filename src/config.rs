@@ -0,0 +1,310 @@
+use crate::{entry, journal_root_dir, parse_utc_offset};
+use chrono::FixedOffset;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Settings are read from this file next to the Journals folder, and then from the same file in the
+// root of the journal that is open, which can override everything except where the journals are.
+pub const CONFIG_FILE_NAME: &str = "journal.toml";
+
+#[derive(Clone)]
+pub struct Config {
+    // where the journals are kept
+    pub journals_dir: String,
+    // how many lines /prev shows at a time, at most as many as fit on the screen
+    pub page_size: usize,
+    // whether new entries are stamped with the full date, time and UTC offset
+    pub iso_timestamps: bool,
+    // the timezone the journal's times are in, or None to use the timezone of this computer
+    pub timezone: Option<FixedOffset>,
+    // what the lines after the first one in a block start with
+    pub indent: String,
+    // how entries are laid out, like "{time} - {text}"
+    pub line_format: String,
+    // in the time breakdowns, gaps between entries longer than this are counted as idle time
+    pub idle_minutes: i64,
+    // the journal is locked after this long without any input
+    pub idle_lock_minutes: i64,
+}
+
+// The settings of the journal that is open. Set when a journal is opened.
+static CURRENT: Mutex<Option<Config>> = Mutex::new(None);
+
+enum Value {
+    Text(String),
+    Number(i64),
+}
+
+fn defaults() -> Config {
+    return Config {
+        journals_dir: String::from("./Journals"),
+        page_size: 40,
+        iso_timestamps: false,
+        timezone: None,
+        indent: String::from("\t"),
+        line_format: String::from(entry::DEFAULT_LINE_FORMAT),
+        idle_minutes: 45,
+        idle_lock_minutes: 10,
+    };
+}
+
+pub fn current() -> Config {
+    return CURRENT.lock().unwrap().clone().unwrap_or_else(defaults);
+}
+
+fn journal_config_path(name: &OsStr) -> PathBuf {
+    let mut path = journal_root_dir(name);
+    path.push(CONFIG_FILE_NAME);
+
+    return path;
+}
+
+// Reads a quoted string, and returns it with whatever comes after the closing quote.
+fn parse_string(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.char_indices();
+    let quote = match chars.next()? {
+        (_, quote) if quote == '"' || quote == '\'' => quote,
+        _ => return None,
+    };
+
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Some((value, &text[i + 1..]));
+        }
+
+        // strings in single quotes are taken as they are
+        if c != '\\' || quote == '\'' {
+            value.push(c);
+            continue;
+        }
+
+        match chars.next()?.1 {
+            't' => value.push('\t'),
+            'n' => value.push('\n'),
+            escaped => value.push(escaped),
+        }
+    }
+
+    return None;
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    if let Some((value, rest)) = parse_string(text) {
+        let rest = rest.trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return None;
+        }
+
+        return Some(Value::Text(value));
+    }
+
+    let number = text.split('#').next().unwrap_or("").trim();
+    return number.parse::<i64>().ok().map(Value::Number);
+}
+
+// Only the `key = value` lines this journal uses are understood, not the rest of TOML.
+fn parse(text: &str) -> Vec<Result<(String, Value), String>> {
+    let mut settings = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let setting = match line.split_once('=') {
+            Some((key, value)) => match parse_value(value.trim()) {
+                Some(value) => Ok((String::from(key.trim()), value)),
+                None => Err(format!("line {}: '{}' isn't a value", i + 1, value.trim())),
+            },
+            None => Err(format!("line {}: expected 'setting = value'", i + 1)),
+        };
+        settings.push(setting);
+    }
+
+    return settings;
+}
+
+fn apply(config: &mut Config, key: &str, value: Value, in_journal: bool) -> Result<(), String> {
+    match (key, value) {
+        ("journals_dir", _) if in_journal => {
+            return Err(String::from(
+                "journals_dir can only be set next to the Journals folder",
+            ));
+        }
+        ("journals_dir", Value::Text(dir)) if !dir.is_empty() => config.journals_dir = dir,
+        ("page_size", Value::Number(size)) if size > 0 => config.page_size = size as usize,
+        ("timestamps", Value::Text(format)) if format == "iso" || format == "short" => {
+            config.iso_timestamps = format == "iso";
+        }
+        ("timezone", Value::Text(timezone)) if timezone == "local" => config.timezone = None,
+        ("timezone", Value::Text(timezone)) if parse_utc_offset(&timezone).is_some() => {
+            config.timezone = parse_utc_offset(&timezone);
+        }
+        ("indent", Value::Text(indent))
+            if !indent.is_empty() && indent.chars().all(|c| c == ' ' || c == '\t') =>
+        {
+            config.indent = indent;
+        }
+        ("idle_minutes", Value::Number(minutes)) if minutes > 0 => config.idle_minutes = minutes,
+        ("idle_lock_minutes", Value::Number(minutes)) if minutes > 0 => {
+            config.idle_lock_minutes = minutes;
+        }
+        ("line_format", Value::Text(format)) if entry::layout(&format).is_some() => {
+            config.line_format = format;
+        }
+        ("line_format", _) => {
+            return Err(String::from(
+                "line_format needs {time} and then {text} at the end, with something between them",
+            ));
+        }
+        ("journals_dir", _)
+        | ("page_size", _)
+        | ("timestamps", _)
+        | ("timezone", _)
+        | ("indent", _)
+        | ("idle_minutes", _)
+        | ("idle_lock_minutes", _) => return Err(format!("{} can't be set to that", key)),
+        _ => return Err(format!("there is no setting called {}", key)),
+    }
+
+    return Ok(());
+}
+
+fn apply_file(config: &mut Config, path: &PathBuf, in_journal: bool, errors: &mut Vec<String>) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return,
+        Err(e) => {
+            errors.push(format!("Couldn't read {:#?}: {}", path, e));
+            return;
+        }
+    };
+
+    for setting in parse(&text) {
+        let result = setting.and_then(|(key, value)| apply(config, &key, value, in_journal));
+        if let Err(e) = result {
+            errors.push(format!("{:#?}, {}.", path, e));
+        }
+    }
+}
+
+// Loads the global settings, and the settings of a journal if one is open. Settings that can't be
+// read are left as they were, and are listed in the error.
+pub fn load(name: Option<&OsStr>) -> Result<(), String> {
+    let mut config = defaults();
+    let mut errors = Vec::new();
+
+    apply_file(
+        &mut config,
+        &PathBuf::from(CONFIG_FILE_NAME),
+        false,
+        &mut errors,
+    );
+
+    if let Some(name) = name {
+        // the journal's settings can only be found once we know where the journals are
+        *CURRENT.lock().unwrap() = Some(config.clone());
+        apply_file(&mut config, &journal_config_path(name), true, &mut errors);
+    }
+
+    *CURRENT.lock().unwrap() = Some(config);
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    return Err(errors.join("\n"));
+}
+
+// Runs `f` with the settings of another journal, for reading it along with the one that is open,
+// and then puts the settings of the open journal back.
+pub fn with_journal<T>(name: &OsStr, f: impl FnOnce() -> T) -> T {
+    let open_journal = CURRENT.lock().unwrap().clone();
+    let _ = load(Some(name));
+
+    let result = f();
+
+    *CURRENT.lock().unwrap() = open_journal;
+    return result;
+}
+
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n");
+
+    return format!("\"{}\"", escaped);
+}
+
+// Changes a setting in the journal's journal.toml, keeping the rest of the file as it is.
+pub fn set(name: &OsStr, key: &str, value: &str) -> Result<(), String> {
+    let path = journal_config_path(name);
+    let text = match fs::read_to_string(&path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        result => result.map_err(|e| format!("Couldn't read {:#?}: {}", path, e))?,
+    };
+
+    let setting = format!("{} = {}", key, quote(value));
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    match lines
+        .iter_mut()
+        .find(|line| line.split_once('=').map(|(k, _)| k.trim()) == Some(key))
+    {
+        Some(line) => *line = setting,
+        None => lines.push(setting),
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    fs::write(&path, text).map_err(|e| format!("Couldn't write {:#?}: {}", path, e))?;
+
+    // settings that couldn't be used were already pointed out when the journal was opened
+    let _ = load(Some(name));
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_settings_override_global_ones() {
+        let mut config = defaults();
+        let global =
+            "# shared\njournals_dir = \"/home/me/Journals\"\npage_size = 20\nindent = '  '";
+        let journal = "line_format = \"[{time}] {text}\"\ntimestamps = \"iso\" # for work\ntimezone = \"+10:00\"\nidle_minutes = 30\nidle_lock_minutes = 0\npage_size = 10\njournals_dir = \"elsewhere\"\nnope = 1";
+
+        for setting in parse(global) {
+            let (key, value) = setting.unwrap();
+            assert!(apply(&mut config, &key, value, false).is_ok());
+        }
+
+        let errors: Vec<String> = parse(journal)
+            .into_iter()
+            .filter_map(|setting| {
+                let (key, value) = setting.ok()?;
+                apply(&mut config, &key, value, true).err()
+            })
+            .collect();
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(config.journals_dir, "/home/me/Journals");
+        assert_eq!(config.page_size, 10);
+        assert_eq!(config.idle_minutes, 30);
+        assert_eq!(config.idle_lock_minutes, 10);
+        assert_eq!(config.indent, "  ");
+        assert_eq!(config.line_format, "[{time}] {text}");
+        assert!(config.iso_timestamps);
+        assert_eq!(config.timezone, FixedOffset::east_opt(10 * 3600));
+        assert!(parse("indent = \"\\t")
+            .iter()
+            .all(|setting| setting.is_err()));
+    }
+}
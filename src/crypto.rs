@@ -0,0 +1,526 @@
+use crate::{get_input_str, journal_day_files, journal_root_dir};
+use console::Term;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// A journal is encrypted if this file is in its root folder. It holds what we need to
+// turn the passphrase back into the key, but never the key itself.
+const ENCRYPTION_FILE_NAME: &str = ".encryption";
+
+// A journal that isn't encrypted can still be locked with a passphrase if this file is in its root folder.
+// It is in the same format as the encryption file, but the key is only used to check the passphrase.
+const LOCK_FILE_NAME: &str = ".lock";
+
+// Every encrypted day file starts with this, so plain files are still readable.
+const MAGIC: &[u8] = b"JOURNAL-ENCRYPTED-1\n";
+
+// Decrypting this is how we know the passphrase was right.
+const CHECK_TEXT: &[u8] = b"journal passphrase check";
+
+// The check is sealed with this instead of a path, so it can't be passed off as a day.
+const CHECK_AAD: &[u8] = b"check";
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+// scrypt with 64 MiB of memory. It's stored with each journal, so it can be raised later.
+const SCRYPT_LOG_N: u8 = 16;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+const MAX_PASSPHRASE_ATTEMPTS: usize = 3;
+
+type Key = [u8; KEY_LEN];
+
+// The keys of the encrypted journals unlocked in this session. They only ever live in memory.
+static UNLOCKED: Mutex<Vec<(OsString, Key)>> = Mutex::new(Vec::new());
+
+struct EncryptionInfo {
+    salt: Vec<u8>,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    check: Vec<u8>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|b| format!("{:02x}", b)).collect();
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    return (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect();
+}
+
+fn encryption_file_path(name: &OsStr) -> PathBuf {
+    let mut path = journal_root_dir(name);
+    path.push(ENCRYPTION_FILE_NAME);
+
+    return path;
+}
+
+fn lock_file_path(name: &OsStr) -> PathBuf {
+    let mut path = journal_root_dir(name);
+    path.push(LOCK_FILE_NAME);
+
+    return path;
+}
+
+fn load_info(path: &Path) -> io::Result<EncryptionInfo> {
+    let text = fs::read_to_string(path)?;
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "the encryption file is corrupted");
+
+    let mut salt = None;
+    let mut check = None;
+    let mut log_n = None;
+    let mut r = None;
+    let mut p = None;
+
+    for line in text.lines() {
+        match line.split_once('=') {
+            Some(("salt", value)) => salt = from_hex(value),
+            Some(("check", value)) => check = from_hex(value),
+            Some(("log_n", value)) => log_n = value.parse::<u8>().ok(),
+            Some(("r", value)) => r = value.parse::<u32>().ok(),
+            Some(("p", value)) => p = value.parse::<u32>().ok(),
+            _ => {}
+        }
+    }
+
+    return Ok(EncryptionInfo {
+        salt: salt.ok_or_else(invalid)?,
+        log_n: log_n.ok_or_else(invalid)?,
+        r: r.ok_or_else(invalid)?,
+        p: p.ok_or_else(invalid)?,
+        check: check.ok_or_else(invalid)?,
+    });
+}
+
+fn save_info(path: &Path, info: &EncryptionInfo) -> io::Result<()> {
+    let text = format!(
+        "salt={}\nlog_n={}\nr={}\np={}\ncheck={}\n",
+        to_hex(&info.salt),
+        info.log_n,
+        info.r,
+        info.p,
+        to_hex(&info.check)
+    );
+
+    return fs::write(path, text);
+}
+
+fn derive_key(passphrase: &str, info: &EncryptionInfo) -> Option<Key> {
+    let params = scrypt::Params::new(info.log_n, info.r, info.p, KEY_LEN).ok()?;
+    let mut key = [0u8; KEY_LEN];
+    scrypt::scrypt(passphrase.as_bytes(), &info.salt, &params, &mut key).ok()?;
+
+    return Some(key);
+}
+
+// Day files are sealed together with where they are in the journal, like "2022/05/21.txt", so an
+// encrypted day can't be passed off as another one by moving or copying the file.
+fn day_aad(path: &Path) -> Vec<u8> {
+    let parts: Vec<String> = path
+        .components()
+        .rev()
+        .take(3)
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    return parts
+        .into_iter()
+        .rev()
+        .collect::<Vec<String>>()
+        .join("/")
+        .into_bytes();
+}
+
+fn seal(key: &Key, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let sealing_key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).unwrap());
+
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .expect("couldn't get random bytes from the system");
+
+    let mut sealed = plaintext.to_vec();
+    sealing_key
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(aad),
+            &mut sealed,
+        )
+        .expect("encrypting failed");
+
+    let mut out = nonce.to_vec();
+    out.append(&mut sealed);
+    return out;
+}
+
+fn open(key: &Key, aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+
+    let opening_key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).ok()?);
+    let nonce = Nonce::try_assume_unique_for_key(&sealed[..NONCE_LEN]).ok()?;
+    let mut in_out = sealed[NONCE_LEN..].to_vec();
+    let plaintext = opening_key
+        .open_in_place(nonce, Aad::from(aad), &mut in_out)
+        .ok()?;
+
+    return Some(plaintext.to_vec());
+}
+
+fn unlocked_key(name: &OsStr) -> Option<Key> {
+    let unlocked = UNLOCKED.lock().unwrap();
+    return unlocked
+        .iter()
+        .find(|(journal, _)| journal == name)
+        .map(|(_, key)| *key);
+}
+
+fn remember_key(name: &OsStr, key: Key) {
+    let mut unlocked = UNLOCKED.lock().unwrap();
+    unlocked.retain(|(journal, _)| journal != name);
+    unlocked.push((OsString::from(name), key));
+}
+
+fn read_passphrase(prompt: &str) -> String {
+    println!("{}", prompt);
+
+    let term = Term::stdout();
+    if term.is_term() {
+        return term.read_secure_line().unwrap_or_default();
+    }
+
+    return get_input_str();
+}
+
+pub fn is_encrypted(name: &OsStr) -> bool {
+    return encryption_file_path(name).is_file();
+}
+
+// Asks for the passphrase of a journal until it is right, or the user runs out of attempts.
+// `info_path` is either the encryption file or the lock file.
+fn ask_for_key(name: &OsStr, info_path: &Path) -> Option<Key> {
+    let info = match load_info(info_path) {
+        Ok(info) => info,
+        Err(e) => {
+            println!("Couldn't unlock [{}]: {}", name.to_string_lossy(), e);
+            return None;
+        }
+    };
+
+    for _ in 0..MAX_PASSPHRASE_ATTEMPTS {
+        let passphrase = read_passphrase(&format!(
+            "[{}] is locked. Enter its passphrase:",
+            name.to_string_lossy()
+        ));
+
+        if let Some(key) = derive_key(&passphrase, &info) {
+            if open(&key, CHECK_AAD, &info.check).as_deref() == Some(CHECK_TEXT) {
+                return Some(key);
+            }
+        }
+
+        println!("That passphrase is wrong.");
+    }
+
+    return None;
+}
+
+pub fn has_lock(name: &OsStr) -> bool {
+    return lock_file_path(name).is_file();
+}
+
+// True if the journal needs a passphrase to be opened, either because it is encrypted or locked.
+pub fn is_protected(name: &OsStr) -> bool {
+    return is_encrypted(name) || has_lock(name);
+}
+
+// Asks for the passphrase of a protected journal if it hasn't been unlocked yet.
+// Returns false if the journal couldn't be unlocked.
+pub fn unlock(name: &OsStr) -> bool {
+    if is_encrypted(name) {
+        if unlocked_key(name).is_some() {
+            return true;
+        }
+
+        return match ask_for_key(name, &encryption_file_path(name)) {
+            Some(key) => {
+                remember_key(name, key);
+                true
+            }
+            None => false,
+        };
+    }
+
+    if has_lock(name) {
+        return ask_for_key(name, &lock_file_path(name)).is_some();
+    }
+
+    return true;
+}
+
+// Forgets everything that was unlocked for this journal, so that the passphrase is needed again.
+pub fn lock(name: &OsStr) {
+    UNLOCKED
+        .lock()
+        .unwrap()
+        .retain(|(journal, _)| journal != name);
+}
+
+// Keeps a journal unlocked after it has been renamed.
+pub fn renamed(old_name: &OsStr, new_name: &OsStr) {
+    if let Some(key) = unlocked_key(old_name) {
+        lock(old_name);
+        remember_key(new_name, key);
+    }
+}
+
+fn new_info(passphrase: &str) -> Result<(EncryptionInfo, Key), String> {
+    let mut salt = vec![0u8; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| String::from("Couldn't get random bytes from the system."))?;
+
+    let mut info = EncryptionInfo {
+        salt,
+        log_n: SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        check: Vec::new(),
+    };
+
+    let key = derive_key(passphrase, &info).ok_or("Couldn't derive a key from the passphrase.")?;
+    info.check = seal(&key, CHECK_AAD, CHECK_TEXT);
+
+    return Ok((info, key));
+}
+
+fn ask_for_new_passphrase(prompt: &str) -> Result<String, String> {
+    let passphrase = read_passphrase(prompt);
+    if passphrase.is_empty() {
+        return Err(String::from("Cancelled, nothing was changed."));
+    }
+
+    if read_passphrase("Enter it again:") != passphrase {
+        return Err(String::from(
+            "The passphrases didn't match, nothing was changed.",
+        ));
+    }
+
+    return Ok(passphrase);
+}
+
+// Adds a passphrase lock to a journal, or changes it if it already has one.
+pub fn set_lock(name: &OsStr) -> Result<String, String> {
+    if is_encrypted(name) {
+        return Err(format!(
+            "[{}] is encrypted, so it already needs its passphrase to be opened.",
+            name.to_string_lossy()
+        ));
+    }
+
+    if has_lock(name) && ask_for_key(name, &lock_file_path(name)).is_none() {
+        return Err(String::from("Wrong passphrase, the lock wasn't changed."));
+    }
+
+    let passphrase = ask_for_new_passphrase(
+        "Enter a passphrase to lock this journal with (leave empty to cancel):",
+    )?;
+    let (info, _) = new_info(&passphrase)?;
+    save_info(&lock_file_path(name), &info)
+        .map_err(|e| format!("Couldn't save the lock file: {}", e))?;
+
+    return Ok(format!(
+        "[{}] now needs its passphrase to be opened.",
+        name.to_string_lossy()
+    ));
+}
+
+pub fn remove_lock(name: &OsStr) -> Result<String, String> {
+    if !has_lock(name) {
+        return Err(format!("[{}] doesn't have a lock.", name.to_string_lossy()));
+    }
+
+    if ask_for_key(name, &lock_file_path(name)).is_none() {
+        return Err(String::from("Wrong passphrase, the lock wasn't removed."));
+    }
+
+    fs::remove_file(lock_file_path(name))
+        .map_err(|e| format!("Couldn't remove the lock file: {}", e))?;
+
+    return Ok(format!(
+        "[{}] no longer has a lock.",
+        name.to_string_lossy()
+    ));
+}
+
+// Turns the contents of a day file back into text. Files that were never encrypted are passed through as they are.
+pub fn decrypt_contents(name: &OsStr, path: &Path, bytes: Vec<u8>) -> io::Result<String> {
+    if !bytes.starts_with(MAGIC) {
+        return String::from_utf8(bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+    }
+
+    let key = unlocked_key(name).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("[{}] is locked", name.to_string_lossy()),
+        )
+    })?;
+
+    let plaintext = open(&key, &day_aad(path), &bytes[MAGIC.len()..]).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            "couldn't decrypt, the file was changed or the key is wrong",
+        )
+    })?;
+
+    return String::from_utf8(plaintext).map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+}
+
+// What should be written to disk for this text. Only encrypted journals change it.
+pub fn encrypt_contents(name: &OsStr, path: &Path, text: &str) -> io::Result<Vec<u8>> {
+    if !is_encrypted(name) {
+        return Ok(text.as_bytes().to_vec());
+    }
+
+    let key = unlocked_key(name).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("[{}] is locked", name.to_string_lossy()),
+        )
+    })?;
+
+    let mut out = MAGIC.to_vec();
+    out.append(&mut seal(&key, &day_aad(path), text.as_bytes()));
+    return Ok(out);
+}
+
+// Rewrites every day file of a journal through `convert`, stopping at the first file that can't be converted.
+fn convert_day_files(
+    name: &OsStr,
+    convert: impl Fn(&Path, Vec<u8>) -> io::Result<Vec<u8>>,
+) -> Result<usize, String> {
+    let files = journal_day_files(name);
+    for path in &files {
+        let converted = fs::read(path).and_then(|bytes| convert(path, bytes));
+        match converted {
+            Ok(bytes) => {
+                if let Err(e) = fs::write(path, bytes) {
+                    return Err(format!("Couldn't write {:#?}: {}", path, e));
+                }
+            }
+            Err(e) => return Err(format!("Couldn't convert {:#?}: {}", path, e)),
+        }
+    }
+
+    return Ok(files.len());
+}
+
+// Turns on encryption for a journal and encrypts everything already in it.
+pub fn enable(name: &OsStr) -> Result<String, String> {
+    if is_encrypted(name) {
+        return Err(format!(
+            "[{}] is already encrypted.",
+            name.to_string_lossy()
+        ));
+    }
+
+    let passphrase =
+        ask_for_new_passphrase("Enter a passphrase for this journal (leave empty to cancel):")?;
+
+    println!("Encrypting, this might take a moment...");
+
+    let (info, key) = new_info(&passphrase)?;
+    remember_key(name, key);
+    save_info(&encryption_file_path(name), &info)
+        .map_err(|e| format!("Couldn't save the encryption file: {}", e))?;
+
+    // the encryption passphrase takes over from the lock
+    let _ = fs::remove_file(lock_file_path(name));
+
+    let count = convert_day_files(name, |path, bytes| {
+        if bytes.starts_with(MAGIC) {
+            return Ok(bytes);
+        }
+
+        let mut out = MAGIC.to_vec();
+        out.append(&mut seal(&key, &day_aad(path), &bytes));
+        return Ok(out);
+    })?;
+
+    return Ok(format!(
+        "[{}] is now encrypted ({} days). Don't forget the passphrase, there is no way to recover it.",
+        name.to_string_lossy(),
+        count
+    ));
+}
+
+// Turns off encryption for an unlocked journal, and writes everything back as plain text.
+pub fn disable(name: &OsStr) -> Result<String, String> {
+    if !is_encrypted(name) {
+        return Err(format!("[{}] isn't encrypted.", name.to_string_lossy()));
+    }
+
+    // always ask, even if it's unlocked, so that someone at an unlocked journal can't turn it off
+    match ask_for_key(name, &encryption_file_path(name)) {
+        Some(key) => remember_key(name, key),
+        None => return Err(String::from("Wrong passphrase, nothing was decrypted.")),
+    }
+
+    let count = convert_day_files(name, |path, bytes| {
+        return decrypt_contents(name, path, bytes).map(String::into_bytes);
+    })?;
+
+    fs::remove_file(encryption_file_path(name))
+        .map_err(|e| format!("Couldn't remove the encryption file: {}", e))?;
+    lock(name);
+
+    return Ok(format!(
+        "[{}] is no longer encrypted ({} days).",
+        name.to_string_lossy(),
+        count
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_only_decrypt_where_they_were_written() {
+        let key = [7u8; KEY_LEN];
+        let day = Path::new("Journals/work/2022/05/21.txt");
+        let aad = day_aad(day);
+        assert_eq!(aad, b"2022/05/21.txt");
+
+        let sealed = seal(&key, &aad, b"09:50 am - hello");
+        assert_eq!(
+            open(&key, &aad, &sealed).as_deref(),
+            Some(&b"09:50 am - hello"[..])
+        );
+        assert_eq!(
+            open(&key, &day_aad(Path::new("2022/05/22.txt")), &sealed),
+            None
+        );
+        assert_eq!(open(&[8u8; KEY_LEN], &aad, &sealed), None);
+        assert_eq!(open(&key, &[], &sealed), None);
+
+        let check = seal(&key, CHECK_AAD, CHECK_TEXT);
+        assert_eq!(open(&key, &aad, &check), None);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{config, timestamp_len};
+
+// How lines are laid out when nothing else is set. Lines in this layout can always be read,
+// so changing the layout doesn't break the days that were written before.
+pub const DEFAULT_LINE_FORMAT: &str = "{time} - {text}";
+
+// Roughly how wide a tab is, when working out how deep lines that aren't indented
+// the way the journal is set up to are.
+const TAB_WIDTH: usize = 4;
+
+// A line with a timestamp. Offsets are in bytes, from the start of the line.
+pub struct Entry<'a> {
+    // 0 for the first line of a block, 1 for the lines after it, and more for lines nested under those
+    pub level: usize,
+    pub stamp: &'a str,
+    pub stamp_start: usize,
+    pub contents_start: usize,
+}
+
+// Splits a line format, like "[{time}] {text}", into what comes before the timestamp, and what
+// comes between it and the text. The text has to come last, so it can run to the end of the line.
+pub fn layout(format: &str) -> Option<(&str, &str)> {
+    let (prefix, rest) = format.split_once("{time}")?;
+    let separator = rest.strip_suffix("{text}")?;
+
+    if separator.is_empty() || format!("{}{}", prefix, separator).contains('{') {
+        return None;
+    }
+
+    return Some((prefix, separator));
+}
+
+fn width(whitespace: &str) -> usize {
+    return whitespace
+        .chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum();
+}
+
+// How deep a line is indented, and how many bytes the indentation takes up.
+pub fn indentation(line: &str, indent: &str) -> (usize, usize) {
+    let len = line.len() - line.trim_start_matches(['\t', ' ']).len();
+    if len == 0 {
+        return (0, 0);
+    }
+
+    let mut level = 0;
+    let mut rest = &line[..len];
+    while let Some(after) = rest.strip_prefix(indent) {
+        level += 1;
+        rest = after;
+    }
+
+    // lines that were indented some other way, like with spaces in a journal that uses tabs,
+    // are still counted as indented
+    if !rest.is_empty() {
+        level += (width(rest) + width(indent) - 1) / width(indent).max(1);
+    }
+
+    return (level.max(1), len);
+}
+
+fn parse_with<'a>(line: &'a str, level: usize, start: usize, format: &str) -> Option<Entry<'a>> {
+    let (prefix, separator) = layout(format)?;
+    if !line[start..].starts_with(prefix) {
+        return None;
+    }
+
+    let stamp_start = start + prefix.len();
+    let stamp_end = stamp_start + timestamp_len(&line[stamp_start..])?;
+
+    // lines without any text might have lost the spaces on the end of the separator
+    let rest = &line[stamp_end..];
+    let contents_start = if rest.starts_with(separator) {
+        stamp_end + separator.len()
+    } else if rest.is_empty() || rest == separator.trim_end() {
+        line.len()
+    } else {
+        return None;
+    };
+
+    return Some(Entry {
+        level,
+        stamp: &line[stamp_start..stamp_end],
+        stamp_start,
+        contents_start,
+    });
+}
+
+// Reads a line in the layout the journal is set up with, or in the default layout.
+pub fn parse(line: &str) -> Option<Entry<'_>> {
+    let config = config::current();
+    let (level, start) = indentation(line, &config.indent);
+
+    return parse_with(line, level, start, &config.line_format)
+        .or_else(|| parse_with(line, level, start, DEFAULT_LINE_FORMAT));
+}
+
+// Splits a line into how deep it is, its timestamp if it has one, and the text after them, so it can
+// be laid out again with format_line in a journal that is set up differently.
+pub fn split_line(line: &str) -> (usize, Option<&str>, &str) {
+    if let Some(entry) = parse(line) {
+        return (
+            entry.level,
+            Some(entry.stamp),
+            &line[entry.contents_start..],
+        );
+    }
+
+    let (level, start) = indentation(line, &config::current().indent);
+    return (level, None, &line[start..]);
+}
+
+// Lays out a line the way the journal is set up to.
+pub fn format_line(level: usize, stamp: Option<&str>, text: &str) -> String {
+    let config = config::current();
+    let (prefix, separator) = layout(&config.line_format).unwrap_or(("", " - "));
+
+    let mut line = config.indent.repeat(level);
+    if let Some(stamp) = stamp {
+        line.push_str(prefix);
+        line.push_str(stamp);
+        line.push_str(separator);
+    }
+    line.push_str(text);
+
+    return line;
+}
+
+pub fn contents(line: &str) -> Option<&str> {
+    return Some(&line[parse(line)?.contents_start..]);
+}
+
+// The level of each line. Lines without a timestamp are part of the entry before them.
+pub fn levels(lines: &[&str]) -> Vec<usize> {
+    let mut levels = Vec::new();
+    let mut last_level: Option<usize> = None;
+
+    for line in lines {
+        match parse(line) {
+            Some(entry) => {
+                levels.push(entry.level);
+                last_level = Some(entry.level);
+            }
+            None => levels.push(last_level.map_or(0, |level| level + 1)),
+        }
+    }
+
+    return levels;
+}
+
+// The level of the last entry in a day, which new lines are added under.
+pub fn last_level(text: &str) -> Option<usize> {
+    let (_header, rest) = text.split_once('\n')?;
+    return rest
+        .split('\n')
+        .rev()
+        .find_map(|line| Some(parse(line)?.level));
+}
+
+// Which lines to show around the ones that were picked out: the lines they are nested under, and
+// the lines nested under them.
+pub fn with_context(levels: &[usize], picked: &[bool]) -> Vec<bool> {
+    let mut shown = picked.to_vec();
+
+    for (i, _) in picked.iter().enumerate().filter(|(_, picked)| **picked) {
+        let mut level = levels[i];
+        for j in (0..i).rev() {
+            if levels[j] < level {
+                shown[j] = true;
+                level = levels[j];
+            }
+        }
+
+        for j in i + 1..levels.len() {
+            if levels[j] <= levels[i] {
+                break;
+            }
+            shown[j] = true;
+        }
+    }
+
+    return shown;
+}
+
+// Whether a day has any entries. The header isn't an entry.
+pub fn has_entries(text: &str) -> bool {
+    return text.split('\n').skip(1).any(|line| parse(line).is_some());
+}
+
+// Splits a day into its header line and its blocks. A block starts at every entry that isn't
+// indented, and at any text that comes after an empty line, unless it is an indented entry.
+pub fn blocks(text: &str) -> (&str, Vec<&str>) {
+    let (header, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let offset = text.len() - rest.len();
+
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    let mut after_empty_line = true;
+    let mut start = offset;
+
+    for line in rest.split('\n') {
+        let end = start + line.len();
+
+        if line.trim().is_empty() {
+            after_empty_line = true;
+        } else {
+            let starts_block = match parse(line) {
+                Some(entry) => entry.level == 0,
+                None => after_empty_line,
+            };
+
+            match blocks.last_mut() {
+                Some(block) if !starts_block => block.1 = end,
+                _ => blocks.push((start, end)),
+            }
+            after_empty_line = false;
+        }
+
+        start = end + 1;
+    }
+
+    let blocks = blocks
+        .into_iter()
+        .map(|(start, end)| &text[start..end])
+        .collect();
+
+    return (header, blocks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_follow_indentation() {
+        let text = "Work - Saturday 2022/5/21\n\n\n09:00 am - emails\n    09:30 am - replied\n\t09:40 am - more\n10:00 am - coding\n\n\nsome notes\n  without times";
+
+        let (header, blocks) = blocks(text);
+        assert_eq!(header, "Work - Saturday 2022/5/21");
+        assert_eq!(
+            blocks,
+            vec![
+                "09:00 am - emails\n    09:30 am - replied\n\t09:40 am - more",
+                "10:00 am - coding",
+                "some notes\n  without times"
+            ]
+        );
+
+        assert_eq!(indentation("    09:30 am - replied", "\t"), (1, 4));
+        assert_eq!(indentation("\t\t09:30 am - replied", "\t"), (2, 2));
+        assert_eq!(layout("[{time}] {text}"), Some(("[", "] ")));
+        assert_eq!(layout("{text} - {time}"), None);
+        assert_eq!(contents("09:00 am -"), Some(""));
+    }
+
+    #[test]
+    fn with_context_keeps_parents_and_children() {
+        let lines = [
+            "09:00 am - project",
+            "\t09:10 am - design",
+            "\t\t09:20 am - sketches",
+            "\t\t09:30 am - review",
+            "\t09:40 am - build",
+            "\t\t09:50 am - tests",
+            "some output",
+        ];
+
+        let levels = levels(&lines);
+        assert_eq!(levels, vec![0, 1, 2, 2, 1, 2, 3]);
+
+        let picked = [false, false, false, false, false, true, false];
+        assert_eq!(
+            with_context(&levels, &picked),
+            vec![true, false, false, false, true, true, true]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{entry, find_tags, iterate_journals_dir, now, parse_time, tracking, Direction};
+use chrono::{DateTime, FixedOffset};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+pub struct Row {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub title: String,
+    // the lines of the block after the first one
+    pub details: Vec<String>,
+    pub tags: String,
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        return format!("\"{}\"", field.replace('"', "\"\""));
+    }
+
+    return String::from(field);
+}
+
+fn tags_in(text: &str) -> String {
+    let mut tags: Vec<&str> = find_tags(text).into_iter().map(|(_, tag)| tag).collect();
+    tags.sort_unstable();
+    tags.dedup();
+
+    return tags.join(" ");
+}
+
+// What a line says, without its timestamp.
+fn line_contents(line: &str) -> &str {
+    return entry::contents(line).unwrap_or_else(|| line.trim());
+}
+
+// Each block becomes a row that lasts until the next block starts. The last block of the day
+// lasts until its last entry.
+pub fn block_rows(date: &DateTime<FixedOffset>, journal_text: &str) -> Vec<Row> {
+    let mut rows: Vec<Row> = Vec::new();
+
+    for block in entry::blocks(journal_text).1 {
+        let lines: Vec<&str> = block.split('\n').collect();
+        let levels = entry::levels(&lines);
+
+        let times: Vec<DateTime<FixedOffset>> = block
+            .split('\n')
+            .filter_map(|line| parse_time(line, date))
+            .collect();
+
+        let start = match times.first() {
+            Some(start) => *start,
+            None => continue,
+        };
+
+        if let Some(previous) = rows.last_mut() {
+            previous.end = start.max(previous.end);
+        }
+
+        rows.push(Row {
+            start,
+            end: *times.last().unwrap_or(&start),
+            title: String::from(line_contents(block.split('\n').next().unwrap_or(""))),
+            // lines nested deeper than the first level are indented, so the details keep their shape
+            details: lines
+                .iter()
+                .zip(levels)
+                .skip(1)
+                .map(|(line, level)| {
+                    format!(
+                        "{}{}",
+                        "  ".repeat(level.saturating_sub(1)),
+                        line_contents(line)
+                    )
+                })
+                .collect(),
+            tags: tags_in(block),
+        });
+    }
+
+    return rows;
+}
+
+fn day_rows(date: &DateTime<FixedOffset>, journal_text: &str) -> Vec<Row> {
+    // a task that was never stopped runs until now if it is today, or until the last entry of the day
+    let until = if date.date() == now().date() {
+        now()
+    } else {
+        journal_text
+            .split('\n')
+            .rev()
+            .find_map(|line| parse_time(line, date))
+            .unwrap_or(*date)
+    };
+
+    // time tracked with /start and /stop is used instead of the blocks when there is any
+    let intervals = tracking::intervals(journal_text, date, until);
+    if intervals.is_empty() {
+        return block_rows(date, journal_text);
+    }
+
+    return intervals
+        .into_iter()
+        .map(|interval| Row {
+            start: interval.start,
+            end: interval.end,
+            tags: tags_in(&interval.task),
+            title: interval.task,
+            details: Vec::new(),
+        })
+        .collect();
+}
+
+// Writes a CSV file with a row for every block from `from` to `to`, and returns where it was written.
+pub fn timesheet(
+    name: &OsStr,
+    from: &DateTime<FixedOffset>,
+    to: &DateTime<FixedOffset>,
+) -> Result<String, String> {
+    let mut csv = String::from("date,start,end,duration,journal,block,tags\n");
+    let mut row_count = 0;
+
+    iterate_journals_dir(name, from, Direction::Forwards, |date, journal_text| {
+        if date.date() > to.date() {
+            return false;
+        }
+
+        for row in day_rows(date, &journal_text) {
+            let hours = (row.end - row.start).num_minutes() as f64 / 60.0;
+            let fields = [
+                date.format("%Y-%m-%d").to_string(),
+                row.start.format("%H:%M").to_string(),
+                row.end.format("%H:%M").to_string(),
+                format!("{:.2}", hours),
+                String::from(name.to_string_lossy()),
+                row.title,
+                row.tags,
+            ];
+
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+            row_count += 1;
+        }
+
+        return true;
+    });
+
+    let path = PathBuf::from(format!(
+        "timesheet-{}-{}-to-{}.csv",
+        name.to_string_lossy(),
+        from.format("%Y-%m-%d"),
+        to.format("%Y-%m-%d")
+    ));
+
+    fs::write(&path, csv).map_err(|e| format!("Couldn't write {:#?}: {}", path, e))?;
+
+    return Ok(format!("Wrote {} rows to {:#?}.", row_count, path));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn blocks_last_until_the_next_block() {
+        let date: DateTime<FixedOffset> = Local.ymd(2022, 5, 21).and_hms(12, 0, 0).into();
+        let text = "Work - Saturday 2022/5/21\n\n\n09:00 am - emails #admin\n\t09:30 am - replied, finally\n\n11:00 am - coding #rust\n\t11:45 am - done #rust";
+
+        let rows: Vec<(String, String, String, String)> = day_rows(&date, text)
+            .into_iter()
+            .map(|row| {
+                (
+                    row.start.format("%H:%M").to_string(),
+                    row.end.format("%H:%M").to_string(),
+                    row.title,
+                    row.tags,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    String::from("09:00"),
+                    String::from("11:00"),
+                    String::from("emails #admin"),
+                    String::from("#admin")
+                ),
+                (
+                    String::from("11:00"),
+                    String::from("11:45"),
+                    String::from("coding #rust"),
+                    String::from("#rust")
+                ),
+            ]
+        );
+        assert_eq!(csv_field("replied, finally"), "\"replied, finally\"");
+    }
+}
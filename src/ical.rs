@@ -0,0 +1,316 @@
+use crate::{
+    export, iterate_journals_dir, journal_time, load_journal_to_write, manage, now, push_block,
+    save_journal, to_journal_time, Direction,
+};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+// Lines in an .ics file shouldn't be longer than this, and are folded onto the next line if they are.
+const MAX_LINE_LEN: usize = 74;
+
+// How long an event is when its block has nothing after it to end it, like a block with a single entry
+// at the end of the day. Calendars hide events that end when they start.
+const MIN_EVENT_MINUTES: i64 = 15;
+
+fn escape(text: &str) -> String {
+    return text
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n");
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push(' '),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+
+    return unescaped;
+}
+
+fn push_folded(ics: &mut String, line: &str) {
+    let chars: Vec<char> = line.chars().collect();
+    for (i, chunk) in chars.chunks(MAX_LINE_LEN).enumerate() {
+        if i > 0 {
+            ics.push(' ');
+        }
+
+        ics.extend(chunk.iter());
+        ics.push_str("\r\n");
+    }
+}
+
+fn ics_time(time: &DateTime<FixedOffset>) -> String {
+    return time
+        .with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string();
+}
+
+// Writes an .ics file with an event for every block from `from` to `to`, and returns where it was written.
+// Each event starts at the block's first entry and ends when the next block starts.
+pub fn export(
+    name: &OsStr,
+    from: &DateTime<FixedOffset>,
+    to: &DateTime<FixedOffset>,
+) -> Result<String, String> {
+    let mut ics = String::new();
+    push_folded(&mut ics, "BEGIN:VCALENDAR");
+    push_folded(&mut ics, "VERSION:2.0");
+    push_folded(&mut ics, "PRODID:-//Rust journal//EN");
+
+    let stamp = ics_time(&now());
+    let mut event_count = 0;
+
+    iterate_journals_dir(name, from, Direction::Forwards, |date, journal_text| {
+        if date.date() > to.date() {
+            return false;
+        }
+
+        let rows = export::block_rows(date, &journal_text);
+        for (i, row) in rows.iter().enumerate() {
+            // the UID only depends on where the block is, so exporting the same days again updates
+            // the events in the calendar instead of adding them twice
+            let mut uid = format!(
+                "{}-{}",
+                ics_time(&row.start),
+                escape(&name.to_string_lossy())
+            );
+            let same_start = rows[..i].iter().filter(|r| r.start == row.start).count();
+            if same_start > 0 {
+                uid.push_str(&format!("-{}", same_start + 1));
+            }
+
+            let end = if row.end > row.start {
+                row.end
+            } else {
+                row.start + Duration::minutes(MIN_EVENT_MINUTES)
+            };
+
+            push_folded(&mut ics, "BEGIN:VEVENT");
+            push_folded(&mut ics, &format!("UID:{}@journal", uid));
+            push_folded(&mut ics, &format!("DTSTAMP:{}", stamp));
+            push_folded(&mut ics, &format!("DTSTART:{}", ics_time(&row.start)));
+            push_folded(&mut ics, &format!("DTEND:{}", ics_time(&end)));
+            push_folded(&mut ics, &format!("SUMMARY:{}", escape(&row.title)));
+            if !row.details.is_empty() {
+                push_folded(
+                    &mut ics,
+                    &format!("DESCRIPTION:{}", escape(&row.details.join("\n"))),
+                );
+            }
+            if !row.tags.is_empty() {
+                let categories: Vec<String> = row
+                    .tags
+                    .split(' ')
+                    .map(|tag| escape(tag.trim_start_matches('#')))
+                    .collect();
+                push_folded(&mut ics, &format!("CATEGORIES:{}", categories.join(",")));
+            }
+            push_folded(&mut ics, "END:VEVENT");
+            event_count += 1;
+        }
+
+        return true;
+    });
+
+    push_folded(&mut ics, "END:VCALENDAR");
+
+    let path = PathBuf::from(format!(
+        "journal-{}-{}-to-{}.ics",
+        name.to_string_lossy(),
+        from.format("%Y-%m-%d"),
+        to.format("%Y-%m-%d")
+    ));
+
+    fs::write(&path, ics).map_err(|e| format!("Couldn't write {:#?}: {}", path, e))?;
+
+    return Ok(format!("Wrote {} events to {:#?}.", event_count, path));
+}
+
+// Reads a DTSTART value. Times ending in Z are in UTC, and anything else is taken as the journal's time.
+// All-day events don't have a time, so they are left out.
+fn parse_ics_time(value: &str) -> Option<DateTime<FixedOffset>> {
+    let is_utc = value.ends_with('Z');
+    let naive = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()?;
+
+    if is_utc {
+        return Some(to_journal_time(&Utc.from_utc_datetime(&naive)));
+    }
+
+    return Some(journal_time(naive));
+}
+
+// The timezone a property is in, from its parameters like ";TZID=Europe/Paris". There is no
+// timezone database to look these up in, so only the names for UTC can be used.
+fn other_timezone(key: &str) -> Option<&str> {
+    let tzid = key
+        .split(';')
+        .skip(1)
+        .find_map(|parameter| parameter.strip_prefix("TZID="))?
+        .trim_matches('"');
+
+    return match tzid {
+        "UTC" | "Etc/UTC" | "GMT" | "Etc/GMT" => None,
+        _ => Some(tzid),
+    };
+}
+
+// The start time and summary of every event in an .ics file, and the timezones of the events that
+// couldn't be read because of theirs.
+fn parse_events(ics: &str) -> (Vec<(DateTime<FixedOffset>, String)>, Vec<String>) {
+    // long lines are folded by starting the next line with a space
+    let unfolded = ics
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut events = Vec::new();
+    let mut skipped = Vec::new();
+    let mut start: Option<DateTime<FixedOffset>> = None;
+    let mut timezone: Option<String> = None;
+    let mut summary = String::new();
+
+    for line in unfolded.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(parts) => parts,
+            None => continue,
+        };
+
+        // parameters like ;TZID=... come after the name of the property
+        let property = key.split(';').next().unwrap_or("");
+        match property {
+            "BEGIN" if value == "VEVENT" => {
+                start = None;
+                timezone = None;
+                summary.clear();
+            }
+            "DTSTART" => {
+                timezone = other_timezone(key).map(String::from);
+
+                // times in UTC don't have to end in Z when their TZID already says so
+                start = if key.contains("TZID=") && !value.ends_with('Z') {
+                    parse_ics_time(&format!("{}Z", value))
+                } else {
+                    parse_ics_time(value)
+                };
+            }
+            "SUMMARY" => summary = unescape(value),
+            "END" if value == "VEVENT" => match (&timezone, start) {
+                (Some(timezone), _) => skipped.push(timezone.clone()),
+                (None, Some(start)) => events.push((start, summary.clone())),
+                (None, None) => {}
+            },
+            _ => {}
+        }
+    }
+
+    return (events, skipped);
+}
+
+// Adds a block for every event on `day` in an .ics file, in order of time with the blocks already there.
+pub fn import(name: &OsStr, path: &str, day: &DateTime<FixedOffset>) -> Result<String, String> {
+    let ics = fs::read_to_string(path).map_err(|e| format!("Couldn't read '{}': {}", path, e))?;
+
+    let (events, mut skipped) = parse_events(&ics);
+    let mut events: Vec<(DateTime<FixedOffset>, String)> = events
+        .into_iter()
+        .filter(|(start, _)| start.date() == day.date())
+        .collect();
+    events.sort_by_key(|(start, _)| *start);
+
+    // events in other timezones might be on any day, so they are always pointed out
+    let skipped_note = if skipped.is_empty() {
+        String::new()
+    } else {
+        let count = skipped.len();
+        skipped.sort();
+        skipped.dedup();
+        format!(
+            " {} events were left out, because their timezone ({}) can't be read. Export them in UTC to import them.",
+            count,
+            skipped.join(", ")
+        )
+    };
+
+    if events.is_empty() {
+        return Err(format!(
+            "There are no events on {} in '{}'.{}",
+            day.format("%Y/%-m/%-d"),
+            path,
+            skipped_note
+        ));
+    }
+
+    let mut imported = String::from("\n");
+    for (start, summary) in &events {
+        push_block(*start, summary, &mut imported);
+    }
+
+    let content = load_journal_to_write(name, day);
+    let (merged, added) = manage::merge_day(&content, &imported, day);
+
+    // events that were imported before are only added once
+    if added == 0 {
+        return Err(format!(
+            "The events from '{}' are already in {}.{}",
+            path,
+            day.format("%Y/%-m/%-d"),
+            skipped_note
+        ));
+    }
+    save_journal(name, day, &merged);
+
+    let already_there = match events.len() - added {
+        0 => String::new(),
+        count => format!(" {} were already there.", count),
+    };
+
+    return Ok(format!(
+        "Added {} events from '{}' to {}.{}{}",
+        added,
+        path,
+        day.format("%Y/%-m/%-d"),
+        already_there,
+        skipped_note
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    #[test]
+    fn parse_events_reads_folded_lines() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART:20220521T090000\r\nSUMMARY:Stand\r\n up\\, with the team\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20220522\r\nSUMMARY:Holiday\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+        let (events, skipped) = parse_events(ics);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, Local.ymd(2022, 5, 21).and_hms(9, 0, 0));
+        assert_eq!(events[0].1, "Standup, with the team");
+        assert!(skipped.is_empty());
+
+        let ics = "BEGIN:VEVENT\r\nDTSTART;TZID=Europe/Paris:20220521T090000\r\nSUMMARY:Call\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nDTSTART;TZID=UTC:20220521T100000\r\nSUMMARY:Sync\r\nEND:VEVENT\r\n";
+        let (events, skipped) = parse_events(ics);
+        assert_eq!(skipped, vec!["Europe/Paris"]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].0,
+            to_journal_time(&Utc.ymd(2022, 5, 21).and_hms(10, 0, 0))
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use console::{Key, Term};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+// Only this many of the most recent lines are kept in a history file.
+const MAX_HISTORY: usize = 500;
+
+pub enum EditResult {
+    Continue,
+    Submit(String),
+    // ctrl+c, or ctrl+d on an empty line
+    Cancel,
+    // the key isn't used for editing, so the caller can do something else with it
+    Unhandled(Key),
+}
+
+// Given the text before the cursor, returns everything the word at the cursor could be completed to.
+type Completer<'a> = Box<dyn Fn(&str) -> Vec<String> + 'a>;
+
+struct CompletionState {
+    word_start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+pub struct LineEditor<'a> {
+    chars: Vec<char>,
+    cursor: usize,
+
+    history: Vec<String>,
+    // None when we aren't looking through the history
+    history_index: Option<usize>,
+    unsubmitted: Vec<char>,
+
+    completer: Completer<'a>,
+    completion: Option<CompletionState>,
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix: Vec<char> = match candidates.first() {
+        Some(first) => first.chars().collect(),
+        None => return String::from(""),
+    };
+
+    for candidate in candidates {
+        let matching = prefix
+            .iter()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        prefix.truncate(matching);
+    }
+
+    return prefix.into_iter().collect();
+}
+
+impl<'a> LineEditor<'a> {
+    pub fn new(history: Vec<String>, completer: impl Fn(&str) -> Vec<String> + 'a) -> Self {
+        LineEditor {
+            chars: Vec::new(),
+            cursor: 0,
+            history,
+            history_index: None,
+            unsubmitted: Vec::new(),
+            completer: Box::new(completer),
+            completion: None,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        return self.chars.iter().collect();
+    }
+
+    fn set_text(&mut self, chars: Vec<char>) {
+        self.cursor = chars.len();
+        self.chars = chars;
+    }
+
+    fn word_start(&self) -> usize {
+        let mut start = self.cursor;
+        while start > 0 && !self.chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+
+        return start;
+    }
+
+    fn replace_word(&mut self, word_start: usize, word: &str) {
+        let tail = self.chars.split_off(self.cursor);
+        self.chars.truncate(word_start);
+        self.chars.extend(word.chars());
+        self.cursor = self.chars.len();
+        self.chars.extend(tail);
+    }
+
+    fn complete(&mut self) {
+        // pressing tab again cycles through the candidates
+        if let Some(state) = &mut self.completion {
+            if state.candidates.len() > 1 {
+                state.index = (state.index + 1) % state.candidates.len();
+                let (word_start, word) = (state.word_start, state.candidates[state.index].clone());
+                self.replace_word(word_start, &word);
+            }
+
+            return;
+        }
+
+        let before_cursor: String = self.chars[..self.cursor].iter().collect();
+        let candidates = (self.completer)(&before_cursor);
+        let word_start = self.word_start();
+        let word: String = self.chars[word_start..self.cursor].iter().collect();
+
+        if candidates.len() == 1 {
+            let completed = format!("{} ", candidates[0]);
+            self.replace_word(word_start, &completed);
+            return;
+        }
+
+        let prefix = common_prefix(&candidates);
+        if prefix.chars().count() > word.chars().count() {
+            self.replace_word(word_start, &prefix);
+            return;
+        }
+
+        if candidates.len() > 1 {
+            self.replace_word(word_start, &candidates[0]);
+            self.completion = Some(CompletionState {
+                word_start,
+                candidates,
+                index: 0,
+            });
+        }
+    }
+
+    fn history_up(&mut self) {
+        let index = match self.history_index {
+            None if self.history.is_empty() => return,
+            None => {
+                self.unsubmitted = self.chars.clone();
+                self.history.len() - 1
+            }
+            Some(0) => return,
+            Some(index) => index - 1,
+        };
+
+        self.history_index = Some(index);
+        self.set_text(self.history[index].chars().collect());
+    }
+
+    fn history_down(&mut self) {
+        match self.history_index {
+            None => {}
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.set_text(self.history[index + 1].chars().collect());
+            }
+            Some(_) => {
+                self.history_index = None;
+                let unsubmitted = std::mem::take(&mut self.unsubmitted);
+                self.set_text(unsubmitted);
+            }
+        }
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> EditResult {
+        if key != Key::Tab {
+            self.completion = None;
+        }
+
+        match key {
+            Key::Enter => {
+                let text = self.text();
+                self.set_text(Vec::new());
+                self.history_index = None;
+                return EditResult::Submit(text);
+            }
+            Key::Char('\x04') if self.chars.is_empty() => return EditResult::Cancel,
+            Key::Tab => self.complete(),
+            Key::ArrowLeft => self.cursor = self.cursor.saturating_sub(1),
+            Key::ArrowRight => self.cursor = (self.cursor + 1).min(self.chars.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.chars.len(),
+            Key::ArrowUp => self.history_up(),
+            Key::ArrowDown => self.history_down(),
+            Key::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.chars.remove(self.cursor);
+                }
+            }
+            Key::Del | Key::Char('\x04') => {
+                if self.cursor < self.chars.len() {
+                    self.chars.remove(self.cursor);
+                }
+            }
+            // ctrl+u
+            Key::Char('\x15') => {
+                self.chars.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            // ctrl+k
+            Key::Char('\x0b') => self.chars.truncate(self.cursor),
+            // ctrl+w
+            Key::Char('\x17') => {
+                let mut start = self.cursor;
+                while start > 0 && self.chars[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.chars[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+
+                self.chars.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Key::Char(c) if !c.is_control() => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            _ => return EditResult::Unhandled(key),
+        }
+
+        return EditResult::Continue;
+    }
+
+    // Writes the prompt and the line to the current row of the terminal, and leaves the cursor where it is being edited.
+    // If the line doesn't fit, only the part around the cursor is shown.
+    pub fn render(&self, term: &Term, prompt: &str) {
+        let width = term.size().1 as usize;
+        let available = width.saturating_sub(prompt.chars().count() + 1).max(1);
+
+        let start = (self.cursor + 1).saturating_sub(available);
+        let end = (start + available).min(self.chars.len());
+        let visible: String = self.chars[start..end].iter().collect();
+
+        let _ = term.clear_line();
+        let _ = term.write_str(prompt);
+        let _ = term.write_str(&visible);
+
+        let after_cursor = end - self.cursor;
+        if after_cursor > 0 {
+            let _ = term.move_cursor_left(after_cursor);
+        }
+
+        let _ = term.flush();
+    }
+}
+
+// Reads a line from the terminal with line editing, without any of the main screen around it.
+// If nothing is pressed for `idle_lock`, this returns "/lock".
+pub fn read_line(
+    term: &Term,
+    history: Vec<String>,
+    completer: impl Fn(&str) -> Vec<String>,
+    idle_lock: Option<Duration>,
+) -> Option<String> {
+    let mut editor = LineEditor::new(history, completer);
+
+    loop {
+        editor.render(term, "");
+
+        if idle_lock.is_some_and(|idle_lock| !wait_for_key(idle_lock)) {
+            let _ = term.write_line("");
+            return Some(String::from("/lock"));
+        }
+
+        let key = match term.read_key() {
+            Ok(key) => key,
+            Err(_) => return None,
+        };
+
+        match editor.handle_key(key) {
+            EditResult::Submit(text) => {
+                let _ = term.write_line("");
+                return Some(text);
+            }
+            EditResult::Cancel => return None,
+            _ => {}
+        }
+    }
+}
+
+// Waits until a key is pressed, or the timeout runs out. Returns false if it timed out.
+#[cfg(unix)]
+pub fn wait_for_key(timeout: Duration) -> bool {
+    let fd = libc::STDIN_FILENO;
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return true;
+        }
+
+        // the terminal only says there is input after enter is pressed unless line buffering is off
+        let original = termios;
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        libc::tcsetattr(fd, libc::TCSANOW, &termios);
+
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ready = libc::poll(&mut pollfd, 1, timeout_ms);
+
+        libc::tcsetattr(fd, libc::TCSANOW, &original);
+
+        // if polling failed, let the caller read the key the normal way
+        return ready != 0;
+    }
+}
+
+#[cfg(windows)]
+pub fn wait_for_key(timeout: Duration) -> bool {
+    use winapi::shared::winerror::WAIT_TIMEOUT;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::synchapi::WaitForSingleObject;
+    use winapi::um::winbase::STD_INPUT_HANDLE;
+
+    let timeout_ms = timeout.as_millis().min((u32::MAX - 1) as u128) as u32;
+
+    unsafe {
+        return WaitForSingleObject(GetStdHandle(STD_INPUT_HANDLE), timeout_ms) != WAIT_TIMEOUT;
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn wait_for_key(_timeout: Duration) -> bool {
+    return true;
+}
+
+pub fn load_history(path: &Path) -> Vec<String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    if lines.len() > MAX_HISTORY {
+        lines.drain(..lines.len() - MAX_HISTORY);
+
+        // keep the file from growing forever
+        let _ = fs::write(path, lines.join("\n") + "\n");
+    }
+
+    return lines;
+}
+
+pub fn append_history(path: &Path, history: &[String], line: &str) {
+    if line.trim().is_empty() || history.last().map(|last| last.as_str()) == Some(line) {
+        return;
+    }
+
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+pub fn completions_for<'b>(word: &str, options: impl Iterator<Item = &'b str>) -> Vec<String> {
+    let word_lower = word.to_lowercase();
+    let mut matches: Vec<String> = options
+        .filter(|option| option.to_lowercase().starts_with(&word_lower))
+        .map(String::from)
+        .collect();
+
+    matches.sort();
+    matches.dedup();
+    return matches;
+}
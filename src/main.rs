@@ -1,20 +1,129 @@
+#![allow(clippy::needless_return)]
+
 use chrono::{self, Datelike, Duration, TimeZone, Timelike, Weekday};
-use chrono::{DateTime, Local};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
 use console::Term;
 use std::ffi::{OsStr, OsString};
 use std::fs::{self};
 use std::io::{self, ErrorKind, Write};
-use std::num::ParseIntError;
 use std::path::{self, Path, PathBuf};
-use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod config;
+mod crypto;
+mod entry;
+mod export;
+mod ical;
+mod line_edit;
+mod manage;
+mod pager;
+mod render;
+mod stats;
+mod templates;
+mod todo;
+mod tracking;
+mod tui;
+
+const HISTORY_FILE_NAME: &str = ".history";
+// In the journals root, holds the name of the journal that was open last.
+const LAST_JOURNAL_FILE_NAME: &str = ".last";
+// How many of the most recent days with entries are looked at when completing tags.
+const TAG_SEARCH_DAYS: usize = 60;
+
+// Whether a journal is open, so that waiting too long for input locks it.
+static JOURNAL_OPEN: AtomicBool = AtomicBool::new(false);
+// Set when the journal should be locked while something other than the main screen was waiting for
+// input. That screen goes back, and the main loop locks the journal.
+static LOCK_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+const COMMANDS: &[&str] = &[
+    "/help",
+    "/exit",
+    "/new",
+    "/switch",
+    "/prev",
+    "/times",
+    "/gtime",
+    "/find",
+    "/findall",
+    "/timeline",
+    "/timesheet",
+    "/timestamps",
+    "/timezone",
+    "/ics",
+    "/import",
+    "/stats",
+    "/template",
+    "/todo",
+    "/done",
+    "/start",
+    "/pause",
+    "/resume",
+    "/stop",
+    "/last",
+    "/encrypt",
+    "/decrypt",
+    "/lock",
+    "/setlock",
+    "/removelock",
+    "/rename",
+    "/delete",
+    "/archive",
+    "/unarchive",
+    "/merge",
+];
+
+// Times in the journal are kept in the journal's timezone, so that their wall clock time is the one
+// the entries are written with. Without a timezone set, the offset is whatever this computer used then.
+fn to_journal_time<Tz: TimeZone>(time: &DateTime<Tz>) -> DateTime<FixedOffset> {
+    return to_journal_time_in(time, config::current().timezone);
+}
+
+fn to_journal_time_in<Tz: TimeZone>(
+    time: &DateTime<Tz>,
+    timezone: Option<FixedOffset>,
+) -> DateTime<FixedOffset> {
+    return match timezone {
+        Some(offset) => time.with_timezone(&offset),
+        None => {
+            let local = time.with_timezone(&Local);
+            local.with_timezone(local.offset())
+        }
+    };
+}
+
+// The journal time for a wall clock time, like the ones written in the entries.
+fn journal_time(wall_time: NaiveDateTime) -> DateTime<FixedOffset> {
+    return journal_time_in(wall_time, config::current().timezone);
+}
 
-const JOURNALS_ROOT_DIR: &str = "./Journals";
+fn journal_time_in(
+    wall_time: NaiveDateTime,
+    timezone: Option<FixedOffset>,
+) -> DateTime<FixedOffset> {
+    if let Some(offset) = timezone {
+        return DateTime::from_utc(wall_time - offset, offset);
+    }
+
+    // the wall time might not exist on this computer, in the hour that is skipped when daylight
+    // savings starts, so it is moved past the gap
+    let local = Local
+        .from_local_datetime(&wall_time)
+        .earliest()
+        .or_else(|| {
+            Local
+                .from_local_datetime(&(wall_time + Duration::hours(1)))
+                .earliest()
+        })
+        .unwrap_or_else(|| Local.from_utc_datetime(&wall_time));
+    return local.with_timezone(local.offset());
+}
 
-fn now() -> DateTime<Local> {
-    Local::now()
+fn now() -> DateTime<FixedOffset> {
+    return to_journal_time(&Utc::now());
 }
 
-fn datestamp(time: &DateTime<Local>) -> String {
+fn datestamp(time: &DateTime<FixedOffset>) -> String {
     format!("{}/{}/{}", time.year(), time.month(), time.day())
 }
 
@@ -26,7 +135,7 @@ fn two_dig_number(num: u32) -> String {
     return format!("{}", num);
 }
 
-fn timestamp(time: &DateTime<Local>) -> String {
+fn timestamp(time: &DateTime<FixedOffset>) -> String {
     let (pm, hour) = time.hour12();
     let am_pm = if pm { "pm" } else { "am" };
 
@@ -38,11 +147,7 @@ fn timestamp(time: &DateTime<Local>) -> String {
     )
 }
 
-fn read_file(path: &PathBuf) -> io::Result<String> {
-    return fs::read_to_string(path);
-}
-
-fn write_file(path: &PathBuf, contents: &str) {
+fn write_file(path: &PathBuf, contents: &[u8]) {
     if let Some(prefix) = path.parent() {
         if let Err(e) = std::fs::create_dir_all(prefix) {
             println!("Could not parent directories for {:#?}: {}", path, e);
@@ -59,23 +164,105 @@ fn write_file(path: &PathBuf, contents: &str) {
 fn journal_root_dir(name: &OsStr) -> PathBuf {
     let mut path = PathBuf::new();
 
-    path.push(JOURNALS_ROOT_DIR);
+    path.push(config::current().journals_dir);
     path.push(name);
 
     return path;
 }
 
-fn journal_dir(name: &OsStr, date: &DateTime<Local>) -> PathBuf {
+// Encrypted journals don't keep a history, since it would have their entries in plain text.
+fn history_path(name: &OsStr) -> Option<PathBuf> {
+    if crypto::is_encrypted(name) {
+        return None;
+    }
+
+    let mut path = journal_root_dir(name);
+    path.push(HISTORY_FILE_NAME);
+
+    return Some(path);
+}
+
+// Handles '/timestamps iso' and '/timestamps short'.
+fn set_timestamp_format(name: &OsStr, format: &str) -> Result<String, String> {
+    let result = match format.trim() {
+        "iso" => "New entries will have full timestamps, like 2022-05-21T09:50:12+10:00.",
+        "short" => "New entries will have short timestamps, like 09:50 am.",
+        _ => return Err(String::from(
+            "Type '/timestamps iso' for full timestamps, or '/timestamps short' for short ones.",
+        )),
+    };
+
+    config::set(name, "timestamps", format.trim())
+        .map_err(|e| format!("Couldn't change the timestamps: {}", e))?;
+    return Ok(String::from(result));
+}
+
+// Reads a UTC offset like +10:00, -03:30 or UTC.
+fn parse_utc_offset(text: &str) -> Option<FixedOffset> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("utc") || text == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let sign = match text.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+
+    let (hours, minutes) = text[1..].split_once(':').unwrap_or((&text[1..], "0"));
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+
+    return FixedOffset::east_opt(sign * seconds);
+}
+
+// Handles '/timezone +10:00' and '/timezone local'.
+fn set_timezone(name: &OsStr, timezone: &str) -> Result<String, String> {
+    let timezone = timezone.trim();
+    let result = if timezone == "local" {
+        String::from("The journal now uses the timezone of this computer.")
+    } else {
+        match parse_utc_offset(timezone) {
+            Some(offset) => format!("The journal's times are now in UTC{}.", offset),
+            None => {
+                return Err(String::from(
+                    "Type /timezone followed by a UTC offset like +10:00 or -05:00, or 'local' to use the timezone of this computer.",
+                ))
+            }
+        }
+    };
+
+    config::set(name, "timezone", timezone)
+        .map_err(|e| format!("Couldn't change the timezone: {}", e))?;
+    return Ok(result);
+}
+
+fn journal_dir(name: &OsStr, date: &DateTime<FixedOffset>) -> PathBuf {
     let mut path = journal_root_dir(name);
 
     path.push(format!("{}", date.year()));
-    path.push(format!("{}", &two_dig_number(date.month())));
+    path.push(two_dig_number(date.month()));
     path.push(format!("{}.txt", &two_dig_number(date.day())));
 
     return path;
 }
 
-fn new_journal_text(name: &OsStr, date: &DateTime<Local>) -> String {
+// The name shown in the header of each day. It can be different to the name of the journal's folder,
+// which is kept safe to use as a path.
+fn journal_display_name(name: &OsStr) -> String {
+    for date in journal_days(name).iter().rev() {
+        if let Ok(text) = load_journal_err(name, date) {
+            let header = text.split('\n').next().unwrap_or("");
+            if let Some((display_name, _)) = header.rsplit_once(" - ") {
+                return String::from(display_name);
+            }
+        }
+    }
+
+    return String::from(name.to_string_lossy());
+}
+
+fn new_journal_text(display_name: &str, date: &DateTime<FixedOffset>) -> String {
     let ds = datestamp(date);
     let weekday = match date.weekday() {
         Weekday::Mon => "Monday",
@@ -87,37 +274,34 @@ fn new_journal_text(name: &OsStr, date: &DateTime<Local>) -> String {
         Weekday::Sun => "Sunday",
     };
 
-    format!("{} - {} {}\n", name.to_string_lossy(), weekday, ds)
+    format!("{} - {} {}\n", display_name, weekday, ds)
 }
 
-fn journal_line(date: &DateTime<Local>, indent: usize, contents: &str) -> String {
-    let mut line = String::from("\n");
-
-    for _i in 0..indent {
-        line.push_str("\t");
-    }
-
-    line.push_str(&timestamp(&date));
-    line.push_str(" - ");
-    line.push_str(&contents);
+fn journal_line(date: &DateTime<FixedOffset>, indent: usize, contents: &str) -> String {
+    let stamp = if config::current().iso_timestamps {
+        date.to_rfc3339_opts(SecondsFormat::Secs, false)
+    } else {
+        timestamp(date)
+    };
 
-    return line;
+    return format!("\n{}", entry::format_line(indent, Some(&stamp), contents));
 }
 
-fn load_journal_err(name: &OsStr, date: &DateTime<Local>) -> Result<String, std::io::Error> {
-    let dir: PathBuf = journal_dir(&name, &date);
+fn load_journal_err(name: &OsStr, date: &DateTime<FixedOffset>) -> Result<String, std::io::Error> {
+    let dir: PathBuf = journal_dir(name, date);
+    let bytes = fs::read(&dir)?;
 
-    return read_file(&dir);
+    return crypto::decrypt_contents(name, &dir, bytes);
 }
 
 // This will initialize a journal if not present.
-fn load_journal(name: &OsStr, date: &DateTime<Local>) -> String {
-    let datestamp = datestamp(&date);
+fn load_journal(name: &OsStr, date: &DateTime<FixedOffset>) -> String {
+    let datestamp = datestamp(date);
     return match load_journal_err(name, date) {
         Ok(str) => str.replace("\r", ""),
         Err(e) => {
             if e.kind() == ErrorKind::NotFound {
-                let text = new_journal_text(name, date);
+                let text = new_journal_text(&journal_display_name(name), date);
                 save_journal(name, date, &text);
                 return text;
             }
@@ -132,25 +316,84 @@ fn load_journal(name: &OsStr, date: &DateTime<Local>) -> String {
     };
 }
 
-fn save_journal(name: &OsStr, date: &DateTime<Local>, text: &str) {
-    let dir: PathBuf = journal_dir(&name, &date);
+// Loads a day to write something into it. Before the first entry of today, its template and the tasks
+// that are still open go in. They aren't added when a day is only looked at, so opening the journal
+// doesn't make a day count as written in.
+fn load_journal_to_write(name: &OsStr, date: &DateTime<FixedOffset>) -> String {
+    let mut text = load_journal(name, date);
 
-    write_file(&dir, &text);
+    if !entry::has_entries(&text) && date.date().naive_local() == now().date().naive_local() {
+        templates::fill_new_day(name, *date, &mut text);
+        todo::carry_over(name, *date, &mut text);
+    }
+
+    return text;
+}
+
+fn save_journal(name: &OsStr, date: &DateTime<FixedOffset>, text: &str) {
+    let dir: PathBuf = journal_dir(name, date);
+
+    match crypto::encrypt_contents(name, &dir, text) {
+        Ok(contents) => write_file(&dir, &contents),
+        Err(e) => println!("Could not save journal {:#?}: {}", dir, e),
+    }
+}
+
+// How long to wait for input before locking the journal, or None when no journal is open.
+fn idle_lock() -> Option<std::time::Duration> {
+    if !JOURNAL_OPEN.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let minutes = config::current().idle_lock_minutes as u64;
+    return Some(std::time::Duration::from_secs(minutes * 60));
+}
+
+// Waits for a key in screens that read keys themselves. Returns false if the journal should be
+// locked instead, because nothing was pressed for a while.
+fn wait_for_input() -> bool {
+    match idle_lock() {
+        Some(idle_lock) if !line_edit::wait_for_key(idle_lock) => {
+            LOCK_REQUESTED.store(true, Ordering::Relaxed);
+            return false;
+        }
+        _ => return true,
+    }
 }
 
 fn get_input_str() -> String {
+    return get_input_str_completing(|_| Vec::new());
+}
+
+fn get_input_str_completing(completer: impl Fn(&str) -> Vec<String>) -> String {
+    let term = Term::stdout();
+    if term.is_term() {
+        return match line_edit::read_line(&term, Vec::new(), completer, idle_lock()) {
+            Some(input) => handle_global_input(input),
+            None => handle_global_input(String::from("/exit")),
+        };
+    }
+
     let stdin = io::stdin();
 
     let mut input = String::from("");
-    if let Err(_) = stdin.read_line(&mut input) {
+    if stdin.read_line(&mut input).is_err() {
         return String::from("");
     }
 
-    input = input.trim_end().to_string();
+    return handle_global_input(input);
+}
+
+// Handles the inputs that work from anywhere, no matter how they were read.
+fn handle_global_input(input: String) -> String {
+    let input = input.trim_end().to_string();
 
     if input == "/exit" {
         clear_screen();
         std::process::exit(0);
+    } else if input == "/lock" && JOURNAL_OPEN.load(Ordering::Relaxed) {
+        LOCK_REQUESTED.store(true, Ordering::Relaxed);
+        return String::from("");
     } else if input.starts_with("?")
         || input.starts_with("/?")
         || input.starts_with("?")
@@ -164,19 +407,50 @@ fn get_input_str() -> String {
     return input;
 }
 
-fn display_journal(name: &OsStr) {
+// The lines shown for today's journal, wrapped to `width` (0 to not wrap). `streak` is from
+// stats::recent_streak, worked out once when the journal was opened.
+fn display_journal_lines(
+    name: &OsStr,
+    width: usize,
+    streak: (usize, Option<NaiveDate>),
+) -> Vec<String> {
     let date = now();
-    let content = load_journal(&name, &date);
+    let content = load_journal(name, &date);
+    let mut lines: Vec<String> = Vec::new();
 
-    let has_no_entries = content.matches("-").count() < 2;
-    if has_no_entries {
-        println!(
-            "You haven't put any entries in [{}] yet.\nType '/help' at any time to find out how.\n\n",
+    let has_entries = entry::has_entries(&content);
+    let current_streak = stats::streak_with_today(streak, date.date().naive_local(), has_entries);
+
+    if !has_entries {
+        lines.push(format!(
+            "You haven't put any entries in [{}] yet.",
             name.to_string_lossy()
-        );
+        ));
+        lines.push(String::from("Type '/help' at any time to find out how."));
+
+        if let (_, Some(last_day)) = streak {
+            lines.push(format!(
+                "Current streak: {} days. Last day with entries: {}.",
+                current_streak,
+                last_day.format("%Y/%-m/%-d")
+            ));
+        }
+
+        lines.push(String::from(""));
+        lines.push(String::from(""));
+    } else if current_streak > 0 {
+        lines.push(render::dim(&format!(
+            "Current streak: {} days.",
+            current_streak
+        )));
     }
 
-    println!("{}", &content);
+    lines.append(&mut render::render_day(&content, None, width, true));
+    return lines;
+}
+
+fn display_journal(name: &OsStr, streak: (usize, Option<NaiveDate>)) {
+    println!("{}", display_journal_lines(name, 0, streak).join("\n"));
 }
 
 fn get_folders(path: &Path) -> Result<Vec<OsString>, io::Error> {
@@ -185,30 +459,94 @@ fn get_folders(path: &Path) -> Result<Vec<OsString>, io::Error> {
     let dir_entries = path.read_dir()?;
 
     dirs = Vec::new();
-    for dir_entry in dir_entries {
-        match dir_entry {
-            Ok(dir) => {
-                let path = dir.path();
-
-                if !path.is_file() {
-                    if let Some(filename) = path.file_name() {
-                        dirs.push(OsString::from(filename));
-                    }
-                }
+    for dir in dir_entries.flatten() {
+        let path = dir.path();
+
+        if !path.is_file() {
+            if let Some(filename) = path.file_name() {
+                dirs.push(OsString::from(filename));
             }
-            _ => {}
         }
     }
 
     return Ok(dirs);
 }
 
+// Every day file of a journal, oldest first.
+fn journal_day_files(name: &OsStr) -> Vec<PathBuf> {
+    let root_dir = journal_root_dir(name);
+    let mut files: Vec<PathBuf> = Vec::new();
+
+    let years = parse_years(&get_folders(&root_dir).unwrap_or_default());
+    for year in years {
+        let year_dir = root_dir.join(format!("{}", year));
+        let mut months = get_folders(&year_dir).unwrap_or_default();
+        months.sort();
+
+        for month in months {
+            let mut days: Vec<PathBuf> = match year_dir.join(&month).read_dir() {
+                Ok(entries) => entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file() && path.extension() == Some(OsStr::new("txt")))
+                    .collect(),
+                Err(_) => continue,
+            };
+
+            days.sort();
+            files.append(&mut days);
+        }
+    }
+
+    return files;
+}
+
+// The date of a day file, from its year/month/day.txt path.
+fn day_file_date(path: &Path) -> Option<DateTime<FixedOffset>> {
+    let day = path.file_stem()?.to_str()?.parse::<u32>().ok()?;
+    let month_dir = path.parent()?;
+    let month = month_dir.file_name()?.to_str()?.parse::<u32>().ok()?;
+    let year = month_dir
+        .parent()?
+        .file_name()?
+        .to_str()?
+        .parse::<i32>()
+        .ok()?;
+
+    return Some(journal_time(
+        NaiveDate::from_ymd_opt(year, month, day)?.and_hms(12, 0, 0),
+    ));
+}
+
+// Every day that has a file in this journal, oldest first.
+fn journal_days(name: &OsStr) -> Vec<DateTime<FixedOffset>> {
+    return journal_day_files(name)
+        .iter()
+        .filter_map(|path| day_file_date(path))
+        .collect();
+}
+
+// Every journal, including archived ones. Folders starting with a dot, like the trash, aren't journals.
+fn get_all_journals() -> Result<Vec<OsString>, io::Error> {
+    let journals_dir = config::current().journals_dir;
+    let mut journals = get_folders(path::Path::new(&journals_dir))?;
+    journals.retain(|journal| !journal.to_string_lossy().starts_with('.'));
+
+    // read_dir has no order, and the numbers in the list of journals shouldn't change between runs
+    journals.sort_by_key(|journal| journal.to_string_lossy().to_lowercase());
+
+    return Ok(journals);
+}
+
+// The journals that can be picked. Archived journals are left out.
 fn get_journals() -> Result<Vec<OsString>, io::Error> {
-    let path = path::Path::new(JOURNALS_ROOT_DIR);
-    let res = get_folders(path);
+    let res = get_all_journals().map(|mut journals| {
+        journals.retain(|journal| !manage::is_archived(journal));
+        journals
+    });
 
     if let Ok(ref journals) = res {
-        if journals.len() == 0 {
+        if journals.is_empty() {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
                 "There are no journals.",
@@ -229,219 +567,858 @@ fn pick_journal() -> OsString {
     return name;
 }
 
-fn pick_new_journal_name() -> OsString {
-    loop {
-        clear_screen();
-        println!("Enter the name of your new journal:");
-        let name = get_input_str();
+// Hides the journal until someone picks a journal again, which needs its passphrase if it has one.
+fn lock_screen(name: &OsStr) -> OsString {
+    JOURNAL_OPEN.store(false, Ordering::Relaxed);
+    LOCK_REQUESTED.store(false, Ordering::Relaxed);
+    crypto::lock(name);
+    clear_screen();
+    println!(
+        "[{}] was locked. Press enter to pick a journal.",
+        name.to_string_lossy()
+    );
+    get_input_str();
 
-        if let Ok(available_journals) = get_journals() {
-            if let Some(existing_name) = find_journal(&name, &available_journals) {
-                println!(
-                    "That name already refers to the journal '{}', please pick another one.",
-                    existing_name.to_string_lossy()
-                );
-            }
+    let name = pick_journal();
+    JOURNAL_OPEN.store(true, Ordering::Relaxed);
+    return name;
+}
+
+// Turns a journal name into the name of its folder: lowercase letters, numbers, - and _,
+// with everything else replaced by a -.
+fn journal_slug(display_name: &str) -> String {
+    let mut slug = String::new();
+    for c in display_name.trim().chars() {
+        if c.is_alphanumeric() || c == '_' {
+            slug.extend(c.to_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
         }
+    }
 
-        let name = OsString::from(name);
-        let date = now();
+    return String::from(slug.trim_matches('-'));
+}
+
+// Checks a name for a new journal, and returns the name of its folder along with the name to show.
+// When a journal is being renamed, it doesn't count as already having the name, so its display name can change.
+fn validate_journal_name(
+    input: &str,
+    renaming: Option<&OsStr>,
+) -> Result<(OsString, String), String> {
+    let display_name = input.trim();
+    if display_name.is_empty() {
+        return Err(String::from("The name can't be empty."));
+    }
+
+    if display_name.contains('/') || display_name.contains('\\') || display_name.contains("..") {
+        return Err(String::from("The name can't have '/', '\\' or '..' in it."));
+    }
+
+    if display_name.contains(" - ") {
+        return Err(String::from("The name can't have ' - ' in it."));
+    }
+
+    let slug = journal_slug(display_name);
+    if slug.is_empty() {
+        return Err(String::from(
+            "The name needs at least one letter or number.",
+        ));
+    }
 
-        if let Err(_) = load_journal_err(&name, &date) {
-            save_journal(&name, &date, &new_journal_text(&name, &date));
+    for existing in get_all_journals().unwrap_or_default() {
+        if Some(existing.as_os_str()) == renaming {
+            continue;
         }
 
-        return name;
+        if existing.to_string_lossy().to_lowercase() == slug
+            || journal_display_name(&existing).to_lowercase() == display_name.to_lowercase()
+        {
+            return Err(format!(
+                "That name already refers to the journal '{}', please pick another one.",
+                existing.to_string_lossy()
+            ));
+        }
     }
+
+    return Ok((OsString::from(slug), String::from(display_name)));
 }
 
-fn pick_journal_from_existing(journals: &Vec<OsString>) -> OsString {
+fn pick_new_journal_name() -> OsString {
+    clear_screen();
+    println!("Enter the name of your new journal:");
+
     loop {
-        clear_screen();
-        print_available_journals(&journals);
-        if journals.len() == 1 {
-            return OsString::from(&journals[0]);
-        }
+        let (name, display_name) = match validate_journal_name(&get_input_str(), None) {
+            Ok(names) => names,
+            Err(e) => {
+                println!("{} Enter another name:", e);
+                continue;
+            }
+        };
 
-        let input = get_input_str();
-        if let Some(value) = find_journal(&input, &journals) {
-            return value;
-        }
+        let date = now();
+        save_journal(&name, &date, &new_journal_text(&display_name, &date));
 
-        println!("Input was invalid, try again");
+        return name;
     }
 }
 
-fn print_available_journals(journals: &Vec<OsString>) {
-    println!("Select a journal:");
-    for (i, journal_name) in journals.iter().enumerate() {
-        println!("[{}] - {}", i, journal_name.to_string_lossy());
-    }
+fn last_journal_path() -> PathBuf {
+    let mut path = PathBuf::from(config::current().journals_dir);
+    path.push(LAST_JOURNAL_FILE_NAME);
+
+    return path;
 }
 
-fn find_journal(input: &str, journals: &Vec<OsString>) -> Option<OsString> {
-    match usize::from_str_radix(&input, 10) {
-        Ok(index) => {
-            if index < journals.len() {
-                return Some(OsString::from(&journals[index]));
-            }
-        }
-        Err(_) => {
-            let input_lower = input.to_ascii_lowercase();
-            for name in journals {
-                let name_lower = String::from(name.to_string_lossy()).to_ascii_lowercase();
-                println!("name_lower {}", name_lower);
-                if name_lower.starts_with(&input_lower) {
-                    return Some(OsString::from(name));
-                }
-            }
-        }
+fn last_journal() -> Option<OsString> {
+    let name = fs::read_to_string(last_journal_path()).ok()?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
     }
 
-    return None;
+    return Some(OsString::from(name));
 }
 
-fn clear_screen() {
-    let term = Term::stdout();
-    term.clear_screen().expect("failed clearing screen");
+fn remember_last_journal(name: &OsStr) {
+    let _ = fs::write(last_journal_path(), name.to_string_lossy().as_bytes());
 }
 
-fn main() {
-    let mut name = pick_journal();
-    let mut message = String::from("");
+fn pick_journal_from_existing(journals: &[OsString]) -> OsString {
+    let last = last_journal().filter(|last| journals.contains(last));
 
     loop {
         clear_screen();
-        display_journal(&name);
-
-        if message.len() > 0 {
-            println!("\n{}\n", &message);
-            message = String::from("");
+        print_available_journals(journals, last.as_deref());
+        if journals.len() == 1 && crypto::unlock(&journals[0]) {
+            return OsString::from(&journals[0]);
         }
 
-        print!("\ncurrent->{}: ", &name.to_string_lossy());
-        if let Err(_) = io::stdout().flush() {
-            //guyse idk how to handle this one
-        }
+        let input = get_input_str_completing(|before_cursor| {
+            line_edit::completions_for(
+                before_cursor,
+                journals.iter().filter_map(|journal| journal.to_str()),
+            )
+        });
 
-        let input = get_input_str();
-        let date = now();
+        let picked = match (input.trim(), &last) {
+            ("", Some(last)) => Some(last.clone()),
+            (input, _) => choose_journal(input, journals),
+        };
 
-        // process input
-        if input.trim() == "" || input.trim() == "-" {
-            continue;
-        } else if input.trim().starts_with("/") {
-            if input.starts_with("/set") || input.starts_with("/switch") {
-                if let Ok(available_journals) = get_journals() {
-                    name = pick_journal_from_existing(&available_journals);
-                } else {
-                    // ideally, this line is never ever reached. I am not sure what the best flow here is
-                    message = String::from("No journals available, use /new to make one.");
-                }
-            } else if input == "/new" {
-                name = pick_new_journal_name();
-            } else if input.starts_with("/last") || input.starts_with("/prev") {
-                display_prev_journals_input_loop(&name, &date, 20);
-            } else if input.starts_with("/time") {
-                display_time_stats(&name, &date, false);
-            } else if input.starts_with("/gtime") {
-                display_time_stats(&name, &date, true);
-            } else if input.starts_with("/find") {
-                find_input_loop(&name, &date);
+        if let Some(value) = picked {
+            if crypto::unlock(&value) {
+                return value;
             }
 
-            continue;
-        } else if input.starts_with("help") {
             continue;
         }
 
-        match append_to_journal(&name, date, input) {
-            Ok(new_content) => {
-                save_journal(&name, &date, &new_content);
-            }
-            Err(e) => {
-                message = e;
-            }
+        println!("Input was invalid, try again");
+    }
+}
+
+fn print_available_journals(journals: &[OsString], last: Option<&OsStr>) {
+    println!("Select a journal:");
+    for (i, journal_name) in journals.iter().enumerate() {
+        if Some(journal_name.as_os_str()) == last {
+            println!(
+                "[{}] - {} (last used, press enter to pick it)",
+                i,
+                journal_name.to_string_lossy()
+            );
+        } else {
+            println!("[{}] - {}", i, journal_name.to_string_lossy());
         }
     }
 }
 
-fn print_help() {
-    clear_screen();
+// True if all of the characters in `input` appear in `name` in the same order, like "wrk" in "work".
+fn is_fuzzy_match(input: &str, name: &str) -> bool {
+    let mut name_chars = name.chars();
+    return input.chars().all(|c| name_chars.any(|n| n == c));
+}
 
-    println!(
-        "
-Help - Saturday 2022/5/21
+// Asks which journal was meant when the input matched more than one.
+fn pick_from_matches(input: &str, matches: &[OsString]) -> Option<OsString> {
+    println!("'{}' could be any of these journals:", input);
+    for (i, journal_name) in matches.iter().enumerate() {
+        println!("[{}] - {}", i, journal_name.to_string_lossy());
+    }
+    println!("Enter the number of the one you meant, or anything else to cancel:");
 
+    let index = get_input_str().trim().parse::<usize>().ok()?;
+    return matches.get(index).cloned();
+}
 
-09:50 am - Before we start:
-    09:50 am - Type / help from anywhere to access this text
-    09:50 am - Type /exit from anywhere to exit this program
+// Finds the journals that the input could mean: by its number in the list, by its name, by the start
+// of its name, or by the letters in its name, in that order. Only the first of those that matches anything is used.
+fn find_journal(input: &str, journals: &[OsString]) -> Vec<OsString> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Vec::new();
+    }
 
-09:51 am - The basics
-    09:51 am - Type any text to add an 'entry'
-    09:51 am - new entries will be added to the current 'block' of lines
-    09:51 am - like
-    09:51 am - this
+    if let Ok(index) = input.parse::<usize>() {
+        return journals.get(index).cloned().into_iter().collect();
+    }
 
-09:52 am - Type dash (-) followed by an entry to start a new block
-    09:52 am - Type a (~) on it's own to toggle the last line between being part of a block vs being the start of a new block
+    let input_lower = input.to_lowercase();
+    let lower = |name: &OsString| name.to_string_lossy().to_lowercase();
 
-09:53 am - (Useful for when you forget a (-) on the line you just entered
+    if let Some(name) = journals.iter().find(|name| lower(name) == input_lower) {
+        return vec![name.clone()];
+    }
 
-09:53 am - Journal Reading
-    09:54 am - Type /prev to view previous entries
-    09:54 am - Type /times to view a time breakdown of how much time elapsed between each block.
-    09:54 am - Type /gtime to show a more granular (but much harder to read) time breakdown between each entry.
+    let matches: Vec<OsString> = journals
+        .iter()
+        .filter(|name| lower(name).starts_with(&input_lower))
+        .cloned()
+        .collect();
+    if !matches.is_empty() {
+        return matches;
+    }
 
-09:54 am - Journal Managing
-    09:54 am - You can have multiple journals.
-    09:54 am - Type /new to create a new journal. You will be asked to provide a name.
-    09:54 am - Type /switch to switch to another journal. This will only work if you have more than one journal.
-"
-    );
+    return journals
+        .iter()
+        .filter(|name| is_fuzzy_match(&input_lower, &lower(name)))
+        .cloned()
+        .collect();
+}
 
-    println!("Press enter to continue...");
-    get_input_str();
+// The journal the input means, asking the user which one if it could be more than one.
+fn choose_journal(input: &str, journals: &[OsString]) -> Option<OsString> {
+    return match find_journal(input, journals).as_slice() {
+        [] => None,
+        [journal] => Some(journal.clone()),
+        matches => pick_from_matches(input.trim(), matches),
+    };
 }
 
-fn display_time_stats(name: &OsStr, date: &DateTime<Local>, granular: bool) {
-    clear_screen();
-    fn parse_time(line: &str, date: &DateTime<Local>) -> Option<DateTime<Local>> {
-        let colon_pos = line.find(":")?;
+// Tags are words starting with a #, like #work. A # in the middle of a word (like in a url) isn't a tag.
+fn find_tags(text: &str) -> Vec<(usize, &str)> {
+    let mut tags = Vec::new();
+    let mut prev_char = ' ';
 
-        let mut hour = line[colon_pos - 2..colon_pos].parse::<u32>().ok()?;
-        let minute = line[colon_pos + 1..colon_pos + 3].parse::<u32>().ok()?;
+    for (i, c) in text.char_indices() {
+        if c == '#' && prev_char.is_whitespace() {
+            let len = text[i + 1..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(text.len() - i - 1);
 
-        if hour != 12 && &line[colon_pos + 4..colon_pos + 6] == "pm" {
-            hour += 12;
+            if len > 0 {
+                tags.push((i, &text[i..i + 1 + len]));
+            }
         }
 
-        let time = date.clone().with_hour(hour)?.with_minute(minute)?;
-
-        return Some(time);
+        prev_char = c;
     }
 
-    let text = load_journal(name, date);
-    let mut times: Vec<(DateTime<Local>, &str)> = Vec::new();
-    let mut start = 0;
+    return tags;
+}
 
-    while let Some(len) = text[start..].find("\n") {
-        let end = start + len;
-        let line = &text[start..end];
-        if let Some(time) = parse_time(line, date) {
-            times.push((time, line));
+fn collect_recent_tags(name: &OsStr, date: &DateTime<FixedOffset>) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let mut days = 0;
+
+    iterate_journals_dir(name, date, Direction::Backwards, |_, journal_text| {
+        for (_, tag) in find_tags(&journal_text) {
+            tags.push(String::from(tag));
         }
 
-        start = end + 1;
+        days += 1;
+        return days < TAG_SEARCH_DAYS;
+    });
+
+    return tags;
+}
+
+// Works out what the word being typed on the main screen could be completed to.
+fn complete_input(name: &OsStr, before_cursor: &str) -> Vec<String> {
+    let word = before_cursor
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or("");
+    let is_first_word = word.len() == before_cursor.len();
+
+    if is_first_word && word.starts_with('/') {
+        return line_edit::completions_for(word, COMMANDS.iter().copied());
     }
 
-    let line = &text[start..];
-    if let Some(time) = parse_time(line, date) {
-        times.push((time, line));
+    if before_cursor.starts_with("/switch ") || before_cursor.starts_with("/set ") {
+        let journals = get_journals().unwrap_or_default();
+        return line_edit::completions_for(
+            word,
+            journals.iter().filter_map(|journal| journal.to_str()),
+        );
     }
 
-    times.push((now(), "<now>"));
+    if word.starts_with('#') {
+        let tags = collect_recent_tags(name, &now());
+        return line_edit::completions_for(word, tags.iter().map(|tag| tag.as_str()));
+    }
+
+    return Vec::new();
+}
+
+fn clear_screen() {
+    let term = Term::stdout();
+    term.clear_screen().expect("failed clearing screen");
+}
+
+// `journal remind [name]` checks if today has any entries without opening the journal, so that it can
+// be used from a shell prompt or cron. It exits with 1 if it doesn't, and 2 if it couldn't tell.
+fn remind(requested: Option<String>) -> i32 {
+    let name = match requested {
+        Some(requested) => manage::find_journal_exact(&requested),
+        None => last_journal(),
+    };
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            println!("Couldn't find the journal to check. Try 'journal remind <name>'.");
+            return 2;
+        }
+    };
+
+    // the journal might have its own timezone or line format. Settings that can't be read
+    // shouldn't stop the reminder, so they are left as they are
+    let _ = config::load(Some(&name));
+
+    let date = now();
+    let has_entries = match load_journal_err(&name, &date) {
+        Ok(text) => entry::has_entries(&text),
+        Err(e) if e.kind() == ErrorKind::NotFound => false,
+        Err(e) => {
+            println!("Couldn't check [{}]: {}", name.to_string_lossy(), e);
+            return 2;
+        }
+    };
+
+    if has_entries {
+        return 0;
+    }
+
+    let (streak, _) = stats::recent_streak(&name);
+    if streak > 0 {
+        println!(
+            "You haven't written in [{}] today. Keep your {} day streak going!",
+            name.to_string_lossy(),
+            streak
+        );
+    } else {
+        println!("You haven't written in [{}] today.", name.to_string_lossy());
+    }
+
+    return 1;
+}
+
+fn main() {
+    let config_errors = config::load(None).err();
+
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("remind") {
+        std::process::exit(remind(args.next()));
+    }
+
+    let mut name = pick_journal();
+    remember_last_journal(&name);
+    let mut message = match config_errors {
+        Some(e) => format!("Some settings couldn't be used:\n{}", e),
+        None => String::new(),
+    };
+    let mut loaded_name: Option<OsString> = None;
+    let mut streak = (0, None);
+
+    let full_screen = Term::stdout().is_term();
+
+    JOURNAL_OPEN.store(true, Ordering::Relaxed);
+    loop {
+        // /lock, or waiting too long for input anywhere, ends up here
+        if LOCK_REQUESTED.load(Ordering::Relaxed) {
+            name = lock_screen(&name);
+        }
+
+        // the settings are read again whenever another journal is opened
+        if loaded_name.as_ref() != Some(&name) {
+            if let Err(e) = config::load(Some(&name)) {
+                message = format!("Some settings couldn't be used:\n{}", e);
+            }
+            loaded_name = Some(name.clone());
+            streak = stats::recent_streak(&name);
+        }
+
+        let input = if full_screen {
+            tui::read_main_input(&name, &message, streak)
+        } else {
+            clear_screen();
+            display_journal(&name, streak);
+
+            if !message.is_empty() {
+                println!("\n{}\n", &message);
+            }
+
+            print!("\ncurrent->{}: ", &name.to_string_lossy());
+            if io::stdout().flush().is_err() {
+                //guyse idk how to handle this one
+            }
+
+            get_input_str()
+        };
+
+        message = String::from("");
+        let date = now();
+
+        // process input
+        if input.trim() == "" || input.trim() == "-" {
+            continue;
+        } else if input.trim().starts_with("/") {
+            let previous_name = name.clone();
+
+            if input == "/setlock" {
+                clear_screen();
+                message = match crypto::set_lock(&name) {
+                    Ok(result) => result,
+                    Err(e) => e,
+                };
+            } else if input == "/removelock" {
+                clear_screen();
+                message = match crypto::remove_lock(&name) {
+                    Ok(result) => result,
+                    Err(e) => e,
+                };
+            } else if input.starts_with("/rename") {
+                let new_name = input.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+                message = match manage::rename(&name, new_name) {
+                    Ok((new_name, result)) => {
+                        name = new_name;
+                        result
+                    }
+                    Err(e) => e,
+                };
+            } else if input == "/delete" {
+                clear_screen();
+                message = match manage::delete(&name) {
+                    Ok(result) => {
+                        name = pick_journal();
+                        result
+                    }
+                    Err(e) => e,
+                };
+            } else if input == "/archive" {
+                message = match manage::archive(&name) {
+                    Ok(result) => result,
+                    Err(e) => e,
+                };
+            } else if input.starts_with("/unarchive") {
+                let requested = input.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+                let journal = match requested.trim() {
+                    "" => Some(name.clone()),
+                    requested => manage::find_journal_exact(requested),
+                };
+
+                message = match journal {
+                    Some(journal) => match manage::unarchive(&journal) {
+                        Ok(result) => result,
+                        Err(e) => e,
+                    },
+                    None => format!("There is no journal called [{}].", requested.trim()),
+                };
+            } else if input.starts_with("/merge") {
+                let names: Vec<&str> = input.split_whitespace().skip(1).collect();
+                message = if names.len() != 2 {
+                    String::from(
+                        "Type /merge <into> <from> to move everything in <from> into <into>.",
+                    )
+                } else {
+                    match (
+                        manage::find_journal_exact(names[0]),
+                        manage::find_journal_exact(names[1]),
+                    ) {
+                        (Some(into), Some(from)) => match manage::merge(&into, &from) {
+                            Ok(result) => {
+                                if name == from {
+                                    name = into;
+                                }
+                                result
+                            }
+                            Err(e) => e,
+                        },
+                        _ => {
+                            String::from("Both journals have to be typed out by their full names.")
+                        }
+                    }
+                };
+            } else if input.starts_with("/set") || input.starts_with("/switch") {
+                if let Ok(available_journals) = get_journals() {
+                    // archived journals aren't in the picker, but can still be opened by name
+                    let all_journals = get_all_journals().unwrap_or_default();
+                    let requested = input.split_once(' ').map(|(_, rest)| rest.trim());
+                    match requested.and_then(|r| choose_journal(r, &all_journals)) {
+                        Some(journal) => {
+                            if crypto::unlock(&journal) {
+                                name = journal;
+                            } else {
+                                message = format!(
+                                    "Couldn't unlock [{}], staying in [{}].",
+                                    journal.to_string_lossy(),
+                                    name.to_string_lossy()
+                                );
+                            }
+                        }
+                        None => name = pick_journal_from_existing(&available_journals),
+                    }
+                } else {
+                    // ideally, this line is never ever reached. I am not sure what the best flow here is
+                    message = String::from("No journals available, use /new to make one.");
+                }
+            } else if input == "/new" {
+                name = pick_new_journal_name();
+            } else if input.starts_with("/last") || input.starts_with("/prev") {
+                pager::display_prev_journals(&name, &date, config::current().page_size);
+            } else if input.starts_with("/stats") {
+                stats::display_stats(&name, input.contains("hist"));
+            } else if input.starts_with("/template") {
+                let requested = input.split_once(' ').map(|(_, rest)| rest.trim());
+                match requested {
+                    Some(template) if !template.is_empty() => {
+                        let mut content = load_journal_to_write(&name, &date);
+                        message = match templates::insert(&name, template, date, &mut content) {
+                            Ok(()) => {
+                                save_journal(&name, &date, &content);
+                                String::new()
+                            }
+                            Err(e) => e,
+                        };
+                    }
+                    _ => {
+                        message = match templates::list(&name) {
+                            templates if templates.is_empty() => String::from(
+                                "There are no templates yet. See /help for how to make one.",
+                            ),
+                            templates => format!("Templates: {}", templates.join(", ")),
+                        }
+                    }
+                }
+            } else if ["/start", "/pause", "/resume", "/stop"]
+                .contains(&input.split(' ').next().unwrap_or(""))
+            {
+                let mut content = load_journal_to_write(&name, &date);
+                message = match tracking::handle_command(&input, date, &mut content) {
+                    Ok(result) => {
+                        save_journal(&name, &date, &content);
+                        result
+                    }
+                    Err(e) => e,
+                };
+            } else if input.starts_with("/timesheet") {
+                let days: Vec<Option<DateTime<FixedOffset>>> = input
+                    .split_whitespace()
+                    .skip(1)
+                    .map(parse_datestamp)
+                    .collect();
+                message = match days.as_slice() {
+                    [] => export::timesheet(&name, &date, &date),
+                    [Some(from)] => export::timesheet(&name, from, &date),
+                    [Some(from), Some(to)] => export::timesheet(&name, from, to),
+                    _ => Err(String::from(
+                        "Type /timesheet followed by the first and last days, like /timesheet 2022/5/1 2022/5/31.",
+                    )),
+                }
+                .unwrap_or_else(|e| e);
+            } else if input.starts_with("/ics") {
+                let days: Vec<Option<DateTime<FixedOffset>>> = input
+                    .split_whitespace()
+                    .skip(1)
+                    .map(parse_datestamp)
+                    .collect();
+                message = match days.as_slice() {
+                    [] => ical::export(&name, &date, &date),
+                    [Some(from)] => ical::export(&name, from, &date),
+                    [Some(from), Some(to)] => ical::export(&name, from, to),
+                    _ => Err(String::from(
+                        "Type /ics followed by the first and last days, like /ics 2022/5/16 2022/5/20.",
+                    )),
+                }
+                .unwrap_or_else(|e| e);
+            } else if input.starts_with("/import") {
+                let rest = input
+                    .split_once(' ')
+                    .map(|(_, rest)| rest.trim())
+                    .unwrap_or("");
+
+                // the day to import is optional, and goes after the file
+                let (path, day) = match rest.rsplit_once(' ') {
+                    Some((path, day)) => match parse_datestamp(day) {
+                        Some(day) => (path.trim(), day),
+                        None => (rest, date),
+                    },
+                    None => (rest, date),
+                };
+
+                message = if path.is_empty() {
+                    String::from("Type /import followed by an .ics file, and optionally the day to import, like /import work.ics 2022/5/21.")
+                } else {
+                    ical::import(&name, path, &day).unwrap_or_else(|e| e)
+                };
+            } else if input.starts_with("/timestamps") {
+                let format = input.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+                message = set_timestamp_format(&name, format).unwrap_or_else(|e| e);
+            } else if input.starts_with("/timezone") {
+                let timezone = input.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+                message = set_timezone(&name, timezone).unwrap_or_else(|e| e);
+            } else if input == "/todo" {
+                todo::display_open_tasks(&name);
+            } else if input.starts_with("/done") {
+                let requested = input.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+                let mut content = load_journal_to_write(&name, &date);
+                message = match todo::mark_done(&name, requested, date, &mut content) {
+                    Ok(task) => {
+                        save_journal(&name, &date, &content);
+                        format!("Ticked off '{}'.", task)
+                    }
+                    Err(e) => e,
+                };
+            } else if input.starts_with("/timeline") {
+                let requested = input.split_once(' ').map(|(_, rest)| rest);
+                match requested.map(parse_datestamp) {
+                    Some(None) => {
+                        message = String::from(
+                            "Type the day as year/month/day, like /timeline 2022/5/21.",
+                        )
+                    }
+                    Some(Some(day)) => display_timeline(&readable_journals(&name), &day),
+                    None => display_timeline(&readable_journals(&name), &date),
+                }
+            } else if input.starts_with("/time") || input.starts_with("/gtime") {
+                let granular = input.starts_with("/gtime");
+                let idle_minutes = input
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|minutes| minutes.parse::<i64>().ok())
+                    .unwrap_or(config::current().idle_minutes);
+                display_time_stats(&name, &date, granular, idle_minutes);
+            } else if input.starts_with("/findall") {
+                find_input_loop(&readable_journals(&name), &date);
+            } else if input.starts_with("/find") {
+                find_input_loop(&[name.clone()], &date);
+            } else if input == "/encrypt" {
+                clear_screen();
+                message = match crypto::enable(&name) {
+                    Ok(result) => {
+                        let mut history = journal_root_dir(&name);
+                        history.push(HISTORY_FILE_NAME);
+                        let _ = fs::remove_file(history);
+
+                        result
+                    }
+                    Err(e) => e,
+                };
+            } else if input == "/decrypt" {
+                clear_screen();
+                message = match crypto::disable(&name) {
+                    Ok(result) => result,
+                    Err(e) => e,
+                };
+            }
+
+            // whatever was unlocked for the old journal has to be unlocked again to get back to it
+            if previous_name != name {
+                crypto::lock(&previous_name);
+                remember_last_journal(&name);
+            }
+
+            continue;
+        } else if input.starts_with("help") {
+            continue;
+        }
+
+        match append_to_journal(&name, date, input) {
+            Ok(new_content) => {
+                save_journal(&name, &date, &new_content);
+            }
+            Err(e) => {
+                message = e;
+            }
+        }
+    }
+}
+
+fn print_help() {
+    clear_screen();
+
+    println!(
+        "
+Help - Saturday 2022/5/21
+
+
+09:50 am - Before we start:
+    09:50 am - Type / help from anywhere to access this text
+    09:50 am - Type /exit from anywhere to exit this program
+    09:50 am - The bar at the bottom of the screen shows the keyboard shortcuts for the commands below, and PageUp/PageDown scroll the journal
+
+09:51 am - The basics
+    09:51 am - Type any text to add an 'entry'
+    09:51 am - new entries will be added to the current 'block' of lines
+    09:51 am - like
+    09:51 am - this
+    09:51 am - Use the arrow keys to edit what you are typing, and up/down to bring back things you typed before
+    09:51 am - Press tab to complete /commands, journal names after /switch, and #tags you have used recently
+
+09:52 am - Type dash (-) followed by an entry to start a new block
+    09:52 am - Type a (~) on it's own to toggle the last line between being part of a block vs being the start of a new block
+    09:52 am - Type (~) followed by a number, like ~3, to toggle that entry of today instead. The numbers are shown next to today's entries. ~3-5 toggles entries 3 to 5, and the lines nested under an entry move along with it.
+    09:52 am - Type (--) or (>>) and a space before an entry to nest it one level deeper than the line before, and (<<) and a space to go back up a level. New entries stay at the same level as the line before.
+
+09:53 am - (Useful for when you forget a (-) on the line you just entered
+
+09:53 am - Templates
+    09:53 am - Put text files in the .templates folder of a journal (like Journals/work/.templates/standup.txt) to use them as templates.
+    09:53 am - Each line of a template is added as if you had typed it, so lines starting with (-) start new blocks.
+    09:53 am - Type /template followed by the name of a template to add it to today, or just /template to see the templates you have.
+    09:53 am - A template called day.txt is added to each day when its first entry is written. A template named after a day of the week, like monday.txt, is used on that day instead.
+
+09:53 am - Tasks
+    09:53 am - Start an entry with [ ] to make it a task, like '[ ] send the report'.
+    09:53 am - Type /todo to see the tasks from the last 30 days that haven't been ticked off yet.
+    09:53 am - Type /done followed by the number of a task from /todo, or the start of it, to tick it off. This adds a line like '[x] send the report' to today.
+    09:53 am - Tasks that are still open are copied into each day when its first entry is written, so they don't get forgotten.
+
+09:53 am - Journal Reading
+    09:54 am - Type /prev to view previous entries
+    09:54 am - Scroll with the arrow keys and PageUp/PageDown, use left/right to jump between days, and q to go back
+    09:54 am - Type /times to view a time breakdown of how much time elapsed between each block.
+    09:54 am - Type /gtime to show a more granular (but much harder to read) time breakdown between each entry.
+    09:54 am - Gaps of more than 45 minutes between entries are shown as idle time, and left out of the time spent on a block. Type /times or /gtime followed by a number of minutes to use a different limit, or set idle_minutes in journal.toml to change it for good.
+    09:54 am - Type /start followed by a task to start tracking time on it, and /stop when you are done. /pause and /resume are for breaks.
+    09:54 am - When a day has tracked time in it, /times and /gtime show that instead of the time between entries.
+    09:54 am - Type /timesheet followed by the first and last days, like /timesheet 2022/5/1 2022/5/31, to save the blocks (or tracked time) in those days to a CSV file. Without any days, it saves today.
+    09:54 am - Type /ics followed by the first and last days to save the blocks in those days as calendar events (.ics), to see them in a calendar app.
+    09:54 am - Type /import followed by an .ics file to add its events for today as blocks. Put a day after the file, like /import work.ics 2022/5/21, to import a different day.
+    09:54 am - Type '/timestamps iso' to stamp new entries with the full date, time and UTC offset (like 2022-05-21T09:50:12+10:00) instead of just the time. '/timestamps short' goes back. Both kinds can be in the same journal.
+    09:54 am - Type /timezone followed by a UTC offset, like /timezone +10:00, to keep the journal in that timezone no matter where your computer is. '/timezone local' goes back to the computer's timezone.
+    09:54 am - Type /find to search the current journal, or /findall to search every journal at once.
+    09:54 am - Type /timeline to see today's blocks from every journal in order of time, or /timeline followed by a day like 2022/5/21 to see another day.
+    09:54 am - Journals with a passphrase are left out of /findall and /timeline, unless they are the current journal.
+    09:54 am - Type /stats to see how many days you have journaled, your streaks, and when you write the most. /stats histogram also draws charts.
+
+09:54 am - Journal Managing
+    09:54 am - You can have multiple journals.
+    09:54 am - Type /new to create a new journal. You will be asked to provide a name.
+    09:54 am - Type /switch to switch to another journal. This will only work if you have more than one journal.
+    09:54 am - You can also type /switch followed by the name of the journal to go straight to it.
+    09:55 am - Type /encrypt to encrypt the current journal with a passphrase. You will need it every time you open the journal.
+    09:55 am - Type /decrypt to turn encryption off again.
+    09:55 am - Type /setlock to need a passphrase to open the current journal, without encrypting it. /removelock takes it off again.
+    09:55 am - Type /lock to hide the journal until it is picked again. This also happens when nothing is pressed for 10 minutes on any screen, or for idle_lock_minutes if that is set in journal.toml.
+    09:56 am - Type /rename followed by a new name to rename the current journal.
+    09:56 am - Type /delete to move the current journal to the trash folder (Journals/.trash). You will be asked to type its name to confirm.
+    09:56 am - Type /archive to hide the current journal from the list of journals. /switch followed by its name still opens it, and /unarchive brings it back.
+    09:56 am - Type /merge followed by two journal names to move every block of the second journal into the first, in order of time.
+"
+    );
+
+    println!("Press enter to continue...");
+    get_input_str();
+}
+
+// How long the full ISO 8601 timestamp at the start of `text` is. They are written with seconds
+// and a UTC offset, like 2022-05-21T09:50:12+10:00, or 2022-05-21T09:50:12Z in UTC.
+fn iso_timestamp_len(text: &str) -> Option<usize> {
+    return [25, 20].iter().copied().find(|len| {
+        text.get(..*len)
+            .is_some_and(|stamp| DateTime::parse_from_rfc3339(stamp).is_ok())
+    });
+}
+
+// Reads a full ISO 8601 timestamp from the start of `text`.
+fn parse_iso_timestamp(text: &str) -> Option<DateTime<FixedOffset>> {
+    let stamp = &text[..iso_timestamp_len(text)?];
+    let time = DateTime::parse_from_rfc3339(stamp).ok()?;
+
+    return Some(to_journal_time(&time));
+}
+
+// How long the timestamp at the start of `text` is, in either format.
+fn timestamp_len(text: &str) -> Option<usize> {
+    if let Some(len) = iso_timestamp_len(text) {
+        return Some(len);
+    }
+
+    let stamp = text.as_bytes().get(..8)?;
+    let is_short = stamp[0..2].iter().all(u8::is_ascii_digit)
+        && stamp[2] == b':'
+        && stamp[3..5].iter().all(u8::is_ascii_digit)
+        && stamp[5] == b' '
+        && (stamp[6] == b'a' || stamp[6] == b'p')
+        && stamp[7] == b'm';
+
+    return if is_short { Some(8) } else { None };
+}
+
+// Reads the timestamp of a journal line, or the first line of a block. Full ISO 8601 timestamps
+// are used as they are, and "hh:mm am" timestamps are put on the given date.
+fn parse_time(line: &str, date: &DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let stamp = entry::parse(line.split('\n').next()?)?.stamp;
+    if let Some(time) = parse_iso_timestamp(stamp) {
+        return Some(time);
+    }
+
+    let mut hour = stamp.get(0..2)?.parse::<u32>().ok()?;
+    let minute = stamp.get(3..5)?.parse::<u32>().ok()?;
+
+    let am_pm = stamp.get(6..8)?;
+    if hour != 12 && am_pm == "pm" {
+        hour += 12;
+    } else if hour == 12 && am_pm == "am" {
+        hour = 0;
+    }
+
+    let wall_time = date.naive_local().date().and_hms_opt(hour, minute, 0)?;
+
+    return Some(journal_time(wall_time));
+}
+
+// Gaps between entries longer than `idle_minutes` are counted as idle rather than as time spent on the block.
+fn display_time_stats(
+    name: &OsStr,
+    date: &DateTime<FixedOffset>,
+    granular: bool,
+    idle_minutes: i64,
+) {
+    clear_screen();
+
+    let text = load_journal(name, date);
+
+    // time that was tracked with /start and /stop is more accurate than the gaps between entries
+    let intervals = tracking::intervals(&text, date, now());
+    if !intervals.is_empty() {
+        tracking::print_report(&intervals);
+        println!("\n\npress enter to go back ...");
+        get_input_str();
+        return;
+    }
+
+    let mut times: Vec<(DateTime<FixedOffset>, &str)> = Vec::new();
+    let mut start = 0;
+
+    while let Some(len) = text[start..].find("\n") {
+        let end = start + len;
+        let line = &text[start..end];
+        if let Some(time) = parse_time(line, date) {
+            times.push((time, line));
+        }
+
+        start = end + 1;
+    }
+
+    let line = &text[start..];
+    if let Some(time) = parse_time(line, date) {
+        times.push((time, line));
+    }
+
+    times.push((now(), "<now>"));
 
     println!(
         "Viewing time breakdown{}:\n\n",
@@ -451,107 +1428,164 @@ fn display_time_stats(name: &OsStr, date: &DateTime<Local>, granular: bool) {
         }
     );
 
-    if times.len() > 0 {
+    let hours = |duration: Duration| (duration.num_minutes() as f64) / 60.0;
+    let idle_threshold = Duration::minutes(idle_minutes);
+    let mut active = Duration::zero();
+    let mut idle = Duration::zero();
+
+    // <now> isn't under anything, so it ends every block
+    let levels: Vec<usize> = times
+        .iter()
+        .map(|(_, line)| entry::parse(line).map_or(0, |entry| entry.level))
+        .collect();
+    let indent = config::current().indent;
+
+    if !times.is_empty() {
         println!("{}", times[0].1);
-        let mut block_time = times[0].0.clone();
+        let mut block_time = times[0].0;
+        let mut block_idle = Duration::zero();
+
+        // the lines with other lines nested under them, with when they started and the idle time so far
+        let mut sub_blocks: Vec<(usize, DateTime<FixedOffset>, Duration)> = Vec::new();
 
         for i in 1..times.len() {
-            let is_block = times[i].1.find("\t") == None;
+            let dt = times[i].0.signed_duration_since(times[i - 1].0);
+            let is_idle = dt > idle_threshold;
+            if is_idle {
+                idle = idle + dt;
+                block_idle = block_idle + dt;
+            } else {
+                active = active + dt;
+            }
+
+            while let Some((level, start, idle_before)) = sub_blocks.last().copied() {
+                if levels[i] > level {
+                    break;
+                }
+
+                sub_blocks.pop();
+                let spent = times[i].0.signed_duration_since(start) - (idle - idle_before);
+                println!(
+                    "{}{:.2}h in this sub-block",
+                    indent.repeat(level + 1),
+                    hours(spent)
+                );
+            }
+
+            let is_block = levels[i] == 0;
             if is_block {
-                println!("");
+                println!();
             }
 
             if is_block || granular {
-                let dt = times[i].0.signed_duration_since(times[i - 1].0);
                 let dt_from_start = times[i].0.signed_duration_since(times[0].0);
-                let dt_from_block = times[i].0.signed_duration_since(block_time);
+                let dt_from_block = times[i].0.signed_duration_since(block_time) - block_idle;
                 println!(
-                    "\nelapsed:\t\tsince start: {:.2}h      since block: {:.2}h      since last: {:.2}h\n",
-                    (dt_from_start.num_minutes() as f64) / 60.0,
-                    (dt_from_block.num_minutes() as f64) / 60.0,
-                    (dt.num_minutes() as f64) / 60.0
+                    "\nelapsed:\t\tsince start: {:.2}h      since block: {:.2}h      since last: {:.2}h{}\n",
+                    hours(dt_from_start),
+                    hours(dt_from_block),
+                    hours(dt),
+                    if is_idle { " (idle)" } else { "" }
                 );
             }
 
+            if is_idle {
+                println!("\nidle:\t\t\t{:.2}h without any entries\n", hours(dt));
+            }
+
             if is_block {
-                block_time = times[i].0.clone();
-                println!("");
+                block_time = times[i].0;
+                block_idle = Duration::zero();
+                println!();
             }
 
             println!("{}", times[i].1);
+
+            if levels[i] > 0 && levels.get(i + 1).is_some_and(|next| *next > levels[i]) {
+                sub_blocks.push((levels[i], times[i].0, idle));
+            }
         }
     }
 
+    println!(
+        "\n\nactive: {:.2}h      idle: {:.2}h      (gaps of more than {} minutes are idle, use '/times <minutes>' or idle_minutes in journal.toml to change this)",
+        hours(active),
+        hours(idle),
+        idle_minutes
+    );
+
     println!("\n\npress enter to go back ...");
     get_input_str();
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 enum Direction {
     Forwards,
     Backwards,
 }
 
+// Parses the year folders in a journal's root, ignoring anything that isn't a year,
+// and sorts them, since read_dir doesn't guarantee any particular order.
+fn parse_years(folders: &[OsString]) -> Vec<i32> {
+    let mut years: Vec<i32> = folders
+        .iter()
+        .filter_map(|folder| folder.to_str()?.parse::<i32>().ok())
+        .collect();
+
+    years.sort_unstable();
+    years.dedup();
+
+    return years;
+}
+
+// The years that iteration will visit, in the order they will be visited.
+// Years on the wrong side of the starting year are skipped entirely, so a starting
+// date before the first year or after the last year is fine.
+fn years_to_visit(years: &[i32], start_year: i32, dir: Direction) -> Vec<i32> {
+    return match dir {
+        Direction::Forwards => years.iter().copied().filter(|y| *y >= start_year).collect(),
+        Direction::Backwards => years
+            .iter()
+            .rev()
+            .copied()
+            .filter(|y| *y <= start_year)
+            .collect(),
+    };
+}
+
 fn iterate_journals_dir(
     name: &OsStr,
-    date: &DateTime<Local>,
+    date: &DateTime<FixedOffset>,
     dir: Direction,
-    mut iter_fn: impl FnMut(&DateTime<Local>, String) -> bool,
+    mut iter_fn: impl FnMut(&DateTime<FixedOffset>, String) -> bool,
 ) {
-    let mut current_date = date.clone();
-
-    fn get_year_index(
-        current_date: &DateTime<Local>,
-        years: &Vec<OsString>,
-    ) -> Result<usize, ParseIntError> {
-        let current_year = current_date.year();
-        for index in 0..years.len() {
-            let year = years[index].to_string_lossy().parse::<i32>()?;
-            if year == current_year {
-                return Ok(index);
-            } else if year > current_year {
-                return Ok(index - 1);
-            }
-        }
+    fn start_of_year(year: i32) -> DateTime<FixedOffset> {
+        return journal_time(NaiveDate::from_ymd(year, 1, 1).and_hms(0, 0, 0));
+    }
 
-        return Ok(years.len());
+    fn end_of_year(year: i32) -> DateTime<FixedOffset> {
+        start_of_year(year + 1) - Duration::days(1)
     }
 
     let root_dir = journal_root_dir(name);
-    let valid_years = match get_folders(&root_dir) {
-        Ok(folders) => folders,
+    let years = match get_folders(&root_dir) {
+        Ok(folders) => parse_years(&folders),
         Err(_) => {
             return;
         }
     };
 
-    let current_year_os_str = OsString::from(format!("{}", current_date.year()));
-    let mut current_year_index = get_year_index(&current_date, &valid_years)
-        .expect("Folders in the journal root must all be 4 digit years");
-
-    fn start_of_year(year: i32) -> DateTime<Local> {
-        return chrono::Local.ymd(year, 1, 1).and_hms(0, 0, 0);
-    }
-
-    fn end_of_year(year: i32) -> DateTime<Local> {
-        start_of_year(year + 1) - Duration::days(1)
-    }
-
-    fn parse_year(str: &OsStr) -> i32 {
-        str.to_string_lossy()
-            .parse::<i32>()
-            .expect("Why isn't this a year. What happened??")
-    }
-
-    if &valid_years[current_year_index] != &current_year_os_str {
-        let new_year = parse_year(&valid_years[current_year_index]);
-        current_date = end_of_year(new_year);
-    }
-
-    loop {
-        let this_year = current_date.year();
+    for year in years_to_visit(&years, date.year(), dir) {
+        let mut current_date = if year == date.year() {
+            *date
+        } else {
+            match dir {
+                Direction::Forwards => start_of_year(year),
+                Direction::Backwards => end_of_year(year),
+            }
+        };
 
-        while this_year == current_date.year() {
+        while year == current_date.year() {
             if let Ok(journal_text) = load_journal_err(name, &current_date) {
                 if !iter_fn(&current_date, journal_text.replace("\r", "")) {
                     return;
@@ -563,307 +1597,674 @@ fn iterate_journals_dir(
                 Direction::Backwards => current_date - Duration::days(1),
             };
         }
-
-        if dir == Direction::Backwards && current_year_index == 0 {
-            return;
-        }
-
-        current_year_index = match dir {
-            Direction::Forwards => current_year_index + 1,
-            Direction::Backwards => current_year_index - 1,
-        };
-
-        if current_year_index >= valid_years.len() {
-            return;
-        }
-
-        let new_year = parse_year(&valid_years[current_year_index]);
-        current_date = match dir {
-            Direction::Forwards => start_of_year(new_year),
-            Direction::Backwards => end_of_year(new_year),
-        };
     }
 }
 
-fn find_input_loop(name: &OsStr, date: &DateTime<Local>) {
-    fn iteration(
-        _name: &OsStr,
-        _date: &DateTime<Local>,
-        find_str: &str,
-        journal_text: &str,
-    ) -> bool {
-        if journal_text.to_lowercase().find(&find_str.to_lowercase()) == None {
-            return true;
-        }
-
-        fn print_highlights(line: &str, find_str_index: usize, symbol: char, count: usize) {
-            print!("    ");
+fn has_search_hit(journal_text: &str, find_str: &str) -> bool {
+    return journal_text
+        .to_lowercase()
+        .contains(&find_str.to_lowercase());
+}
 
-            let mut current_index = 0 as usize;
-            for c in line.chars() {
-                if c == '\t' {
-                    print!("\t");
-                } else {
-                    print!(" ");
-                }
+fn print_search_hits(find_str: &str, journal_text: &str) {
+    fn print_highlights(line: &str, find_str_index: usize, symbol: char, count: usize) {
+        print!("    ");
 
-                current_index += 1;
-                if current_index == find_str_index {
-                    break;
-                }
+        let mut current_index = 0_usize;
+        for c in line.chars() {
+            if c == '\t' {
+                print!("\t");
+            } else {
+                print!(" ");
             }
 
-            for _ in 0..count {
-                print!("{}", symbol);
+            current_index += 1;
+            if current_index == find_str_index {
+                break;
             }
+        }
 
-            println!();
+        for _ in 0..count {
+            print!("{}", symbol);
         }
 
-        // find the block where the text is.
-        for block in journal_text.split("\n\n") {
-            if let Some(_block_index) = block
-                .to_ascii_lowercase()
-                .find(&find_str.to_ascii_lowercase())
-            {
-                // print each line, and highlight the one containing the result
-                for line in block.split("\n") {
-                    match line
-                        .to_ascii_lowercase()
-                        .find(&find_str.to_ascii_lowercase())
-                    {
-                        Some(index) => {
-                            println!("");
-                            print_highlights(line, index, 'v', find_str.len());
-                            println!("--> {}     <--", line);
-                            print_highlights(line, index, '^', find_str.len());
-                            println!("");
-                        }
-                        None => {
-                            println!("    {}", line);
-                        }
+        println!();
+    }
+
+    let (header, blocks) = entry::blocks(journal_text);
+
+    // find the block where the text is.
+    for block in std::iter::once(header).chain(blocks) {
+        if let Some(_block_index) = block
+            .to_ascii_lowercase()
+            .find(&find_str.to_ascii_lowercase())
+        {
+            let lines: Vec<&str> = block.split('\n').collect();
+            let hits: Vec<bool> = lines
+                .iter()
+                .map(|line| has_search_hit(line, find_str))
+                .collect();
+
+            // only the lines with hits are shown, with what they are nested in and what is nested under them
+            let mut shown = entry::with_context(&entry::levels(&lines), &hits);
+            shown[0] = true;
+
+            // print each line, and highlight the one containing the result
+            let mut skipped = false;
+            for (line, shown) in lines.iter().copied().zip(shown) {
+                if !shown {
+                    if !skipped {
+                        println!("    ...");
+                    }
+                    skipped = true;
+                    continue;
+                }
+                skipped = false;
+
+                let is_header = std::ptr::eq(line, header);
+                let rendered = render::render_line(line, is_header, Some(find_str), 0).join("");
+
+                match line
+                    .to_ascii_lowercase()
+                    .find(&find_str.to_ascii_lowercase())
+                {
+                    // the search hits already stand out with colors, so they don't need to be pointed at
+                    Some(_) if console::colors_enabled() => {
+                        println!("--> {}", rendered);
+                    }
+                    Some(index) => {
+                        println!();
+                        print_highlights(line, index, 'v', find_str.len());
+                        println!("--> {}     <--", line);
+                        print_highlights(line, index, '^', find_str.len());
+                        println!();
+                    }
+                    None => {
+                        println!("    {}", rendered);
                     }
                 }
             }
         }
+    }
 
-        //print journal heading
-        match journal_text.find("\n\n") {
-            None => return true,
-            Some(index) => println!("\n\nFound results in {}:\n", &journal_text[0..index]),
-        }
+    //print journal heading
+    println!(
+        "\n\nFound results in {}:\n",
+        render::render_line(header, true, None, 0).join("")
+    );
+}
+
+// Finds the closest day with a search hit in any of the journals, starting at `date` and going in `dir`.
+fn find_next_hit(
+    journals: &[OsString],
+    date: &DateTime<FixedOffset>,
+    dir: Direction,
+    find_str: &str,
+) -> Option<DateTime<FixedOffset>> {
+    let mut closest: Option<DateTime<FixedOffset>> = None;
+
+    for journal in journals {
+        iterate_journals_dir(journal, date, dir, |date, journal_text| {
+            if !has_search_hit(&journal_text, find_str) {
+                return true;
+            }
 
-        return false;
+            let is_closer = match (closest, dir) {
+                (None, _) => true,
+                (Some(closest), Direction::Forwards) => *date < closest,
+                (Some(closest), Direction::Backwards) => *date > closest,
+            };
+            if is_closer {
+                closest = Some(*date);
+            }
+
+            return false;
+        });
     }
 
-    let mut current_date = date.clone();
+    return closest;
+}
+
+// Searches the given journals one day at a time. When there is more than one journal,
+// the hits from all of them on the same day are shown together.
+fn find_input_loop(journals: &[OsString], date: &DateTime<FixedOffset>) {
+    let mut current_date = *date;
     let mut find_str = String::from("");
 
     clear_screen();
     loop {
-        if &find_str != "" {
+        if !find_str.is_empty() {
             println!("Searching for \"{}\"", &find_str);
         }
 
-        if let Ok(find_str_input) = get_input::<String>(
-            "Enter search text, or \">\" to go forwards or backwards, or \":quit\" to go back",
-        ) {
-            clear_screen();
-            let new_date;
-
-            if find_str_input.trim() == "<" || find_str_input.trim() == "" {
-                new_date = current_date - Duration::days(1);
-                println!("searching backwards from {} ...", new_date);
-                iterate_journals_dir(
-                    name,
-                    &new_date,
-                    Direction::Backwards,
-                    |date, journal_text| {
-                        current_date = date.clone();
-                        return iteration(name, date, &find_str, &journal_text);
-                    },
-                );
-            } else if find_str_input.trim() == ">" {
-                new_date = current_date + Duration::days(1);
-                println!("searching forwards from {} ...", new_date);
-                iterate_journals_dir(
-                    name,
-                    &new_date,
-                    Direction::Forwards,
-                    |date, journal_text| {
-                        current_date = date.clone();
-                        return iteration(name, date, &find_str, &journal_text);
-                    },
-                );
-            } else if find_str_input.trim() == ":quit" {
-                break;
-            } else {
-                find_str = find_str_input;
-                current_date = date.clone();
+        println!(
+            "Enter search text, or \">\" to go forwards or backwards, or \":quit\" to go back"
+        );
+        let find_str_input = get_input_str();
+        clear_screen();
+
+        if LOCK_REQUESTED.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let dir = if find_str_input.trim() == "<" || find_str_input.trim() == "" {
+            Direction::Backwards
+        } else if find_str_input.trim() == ">" {
+            Direction::Forwards
+        } else if find_str_input.trim() == ":quit" {
+            break;
+        } else {
+            find_str = find_str_input;
+            // the first step back should also look at the starting day
+            current_date = *date + Duration::days(1);
+            continue;
+        };
+
+        let new_date = match dir {
+            Direction::Forwards => current_date + Duration::days(1),
+            Direction::Backwards => current_date - Duration::days(1),
+        };
+        match dir {
+            Direction::Forwards => println!("searching forwards from {} ...", new_date),
+            Direction::Backwards => println!("searching backwards from {} ...", new_date),
+        }
+
+        match find_next_hit(journals, &new_date, dir, &find_str) {
+            Some(hit_date) => {
+                current_date = hit_date;
+                for journal in journals {
+                    if let Ok(journal_text) = load_journal_err(journal, &hit_date) {
+                        let journal_text = journal_text.replace("\r", "");
+                        if has_search_hit(&journal_text, &find_str) {
+                            config::with_journal(journal, || {
+                                print_search_hits(&find_str, &journal_text)
+                            });
+                        }
+                    }
+                }
             }
+            None => println!("No more results.\n"),
         }
     }
 }
 
-fn display_prev_journals(name: &OsStr, date: &DateTime<Local>, page_size: u32, mut page_num: u32) {
-    clear_screen();
-    if page_num > 0 {
-        page_num -= 1;
+// The journals that can be looked at together with the current one without asking for any passphrases.
+// Archived journals are only hidden from the list of journals, so they are still searched.
+fn readable_journals(name: &OsStr) -> Vec<OsString> {
+    let mut journals: Vec<OsString> = get_all_journals()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|journal| journal == name || !crypto::is_protected(journal))
+        .collect();
+
+    if !journals.iter().any(|journal| journal == name) {
+        journals.push(OsString::from(name));
     }
 
-    let start = page_size * page_num;
-    let end = start + page_size;
+    return journals;
+}
 
-    let mut count = 0;
-    let mut journals: Vec<String> = Vec::new();
+// Shows the blocks of several journals on the same day, in order of time, with the journal each came from.
+fn display_timeline(journals: &[OsString], date: &DateTime<FixedOffset>) {
+    clear_screen();
 
-    iterate_journals_dir(
-        name,
-        date,
-        Direction::Backwards,
-        |_current_date, journal_text| {
-            if count >= start {
-                journals.push(journal_text);
-            }
+    let mut blocks: Vec<(Option<DateTime<FixedOffset>>, &OsString, String)> = Vec::new();
+    for journal in journals {
+        let text = match load_journal_err(journal, date) {
+            Ok(text) => text.replace("\r", ""),
+            Err(_) => continue,
+        };
 
-            count += 1;
+        let (_, journal_blocks) =
+            config::with_journal(journal, || manage::timed_blocks(&text, date));
+        for (time, block) in journal_blocks {
+            blocks.push((time, journal, String::from(block)));
+        }
+    }
 
-            if count >= end {
-                return false;
-            }
+    blocks.sort_by_key(|(time, _, _)| *time);
 
-            return true;
-        },
+    println!(
+        "{}\n",
+        render::render_line(&format!("Timeline - {}", datestamp(date)), true, None, 0).join("")
     );
 
-    journals.reverse();
-    for (i, journal_text) in journals.iter().enumerate() {
-        let entry_count = if page_num == 0 && i == (journals.len() - 1) {
-            String::from("")
-        } else {
-            format!(" - {}", (start as usize) + journals.len() - 1 - i)
-        };
-
-        println!(
-            "\n\n\n---------------- <latest entry{}> ----------------\n\n",
-            &entry_count
-        );
-
-        println!("{}", &journal_text);
+    if blocks.is_empty() {
+        println!("None of the journals have entries on this day.");
     }
 
-    println!("\n\n");
-    if journals.len() == 0 {
+    for (_, journal, block) in blocks {
         println!(
-            "No entries were found for page {} with a page size of {}.",
-            page_num, page_size
+            "\n{}",
+            render::dim(&format!("[{}]", journal.to_string_lossy()))
         );
-    } else {
-        println!(
-            "Viewing entries from latest-{} to latest-{}",
-            start + (journals.len() as u32) - 1,
-            start
-        );
-        if journals.len() != page_size as usize {
-            println!("(Only {}/{} entries were found)", journals.len(), page_size);
+        for line in block.split('\n') {
+            println!("{}", render::render_line(line, false, None, 0).join(""));
         }
     }
+
+    println!("\n\npress enter to go back ...");
+    get_input_str();
 }
 
-fn get_input<T: std::str::FromStr>(message: &str) -> Result<T, <T as FromStr>::Err> {
-    println!("{}", message);
-    let input = get_input_str();
+// Reads a "year/month/day" date, like the ones in the journal headers.
+fn parse_datestamp(text: &str) -> Option<DateTime<FixedOffset>> {
+    let mut parts = text.trim().split('/').map(|part| part.parse::<u32>().ok());
+    let year = parts.next()?? as i32;
+    let month = parts.next()??;
+    let day = parts.next()??;
+    if parts.next().is_some() {
+        return None;
+    }
 
-    return input.parse::<T>();
+    return Some(journal_time(
+        NaiveDate::from_ymd_opt(year, month, day)?.and_hms(12, 0, 0),
+    ));
 }
 
-fn display_prev_journals_input_loop(name: &OsStr, date: &DateTime<Local>, page_size: u32) {
-    let mut page = 0;
-    loop {
-        display_prev_journals(name, date, page_size, page);
-        if let Ok(page_num) =
-            get_input::<u32>("input a page number (1 or more), or anything else to go back")
-        {
-            page = page_num;
-        } else {
-            break;
-        }
+fn append_to_journal(
+    name: &OsStr,
+    date: DateTime<FixedOffset>,
+    input: String,
+) -> Result<String, String> {
+    // '~' only changes the entries that are already there
+    let is_toggle = input
+        .trim()
+        .strip_prefix('~')
+        .and_then(parse_toggle)
+        .is_some();
+    let mut content = if is_toggle {
+        load_journal(name, &date)
+    } else {
+        load_journal_to_write(name, &date)
+    };
+    add_input(date, &input, &mut content)?;
+
+    Ok(content)
+}
+
+// Where an entry goes, from what it starts with.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Placement {
+    // '-' starts a new block
+    Block,
+    // '--' or '>> ' nests it one level deeper than the line before
+    Deeper,
+    // '<< ' goes back up a level
+    Shallower,
+    // anything else stays at the same level as the line before
+    Same,
+}
+
+// Splits off the marker an entry starts with. '>>' and '<<' need a space after them, so that
+// text like '>>=' or '<<<' is still just text.
+fn split_placement(input: &str) -> (Placement, &str) {
+    if let Some(rest) = input.strip_prefix("--") {
+        return (Placement::Deeper, rest);
+    }
+
+    if let Some(rest) = input.strip_prefix(">> ") {
+        return (Placement::Deeper, rest);
     }
+
+    if let Some(rest) = input.strip_prefix("<< ") {
+        return (Placement::Shallower, rest);
+    }
+
+    if let Some(rest) = input.strip_prefix('-') {
+        return (Placement::Block, rest);
+    }
+
+    return (Placement::Same, input);
 }
 
-fn append_to_journal(name: &OsStr, date: DateTime<Local>, input: String) -> Result<String, String> {
-    let mut content = load_journal(name, &date);
-    let has_no_entries = content.matches("-").count() < 2;
-    if input.trim() == "~" {
-        if !has_no_entries {
-            toggle_block(&mut content);
-        } else {
-            return Err(String::from("Can't use '~' when there aren't any entries"));
-        }
-    } else if input.starts_with("-") || has_no_entries {
-        let mut input = input.trim();
-        if input.starts_with("-") {
-            input = &input[1..];
-        }
+// Adds a line that was typed in to a day's text. Lines go under the last entry, or start a
+// new block after a dash (-), while '--text' or '>> text' go one level deeper and '<< text' one level up.
+fn add_input(date: DateTime<FixedOffset>, input: &str, content: &mut String) -> Result<(), String> {
+    let last_level = entry::last_level(content);
+    let level = last_level.unwrap_or(0).max(1);
 
-        push_block(date, &input, &mut content);
+    let toggle = input.trim().strip_prefix('~').and_then(parse_toggle);
+    let (placement, text) = split_placement(input);
+
+    if let Some(entries) = toggle {
+        toggle_blocks(content, entries)?;
+    } else if last_level.is_none() {
+        // the first entry of the day always starts a block, whatever it starts with
+        push_block(date, text, content);
     } else {
-        push_line(date, input, &mut content);
+        match placement {
+            Placement::Block => push_block(date, text, content),
+            Placement::Deeper => push_line(date, last_level.unwrap_or(0) + 1, text, content),
+            Placement::Shallower => push_line(date, (level - 1).max(1), text, content),
+            Placement::Same => push_line(date, level, text, content),
+        }
     }
 
-    Ok(content)
+    Ok(())
 }
 
-fn push_block(date: DateTime<Local>, input: &str, content: &mut String) {
+fn push_block(date: DateTime<FixedOffset>, input: &str, content: &mut String) {
     let new_line = journal_line(&date, 0, input.trim());
-    content.push_str("\n");
+    content.push('\n');
     content.push_str(&new_line);
 }
 
-fn push_line(date: DateTime<Local>, input: String, content: &mut String) {
-    let new_line = journal_line(&date, 1, input.trim());
+fn push_line(date: DateTime<FixedOffset>, level: usize, input: &str, content: &mut String) {
+    let new_line = journal_line(&date, level, input.trim());
     content.push_str(&new_line);
 }
 
-fn toggle_block(content: &mut String) {
-    fn newlines_to_nl_tab(content: &mut String, newlines: usize) {
-        let mut new_content = String::from("");
-        new_content.push_str(&content[0..newlines]);
-        new_content.push_str("\n\t");
-        new_content.push_str(&content[(newlines + 2)..]);
-        content.clear();
-        content.push_str(&new_content[..]);
+// Reads what comes after a '~': nothing for the last entry, the number of an entry counting from 1,
+// or a range of them like 3-5. Returns None when it isn't any of those, so it is just text.
+fn parse_toggle(text: &str) -> Option<Option<(usize, usize)>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(None);
     }
 
-    fn nl_tab_to_newlines(content: &mut String, nl_tab: usize) {
-        let mut new_content = String::from("");
-        new_content.push_str(&content[0..nl_tab]);
-        new_content.push_str("\n\n");
-        new_content.push_str(&content[(nl_tab + 2)..]);
-        content.clear();
-        content.push_str(&new_content[..]);
+    let (first, last) = text.split_once('-').unwrap_or((text, text));
+    let first = first.trim().parse::<usize>().ok()?;
+    let last = last.trim().parse::<usize>().ok()?;
+
+    return Some(Some((first.min(last), first.max(last))));
+}
+
+// Toggles some of the day's entries between starting a block and being part of the block before them.
+// Either all of them are toggled, or none are.
+fn toggle_blocks(content: &mut String, entries: Option<(usize, usize)>) -> Result<(), String> {
+    let mut toggled = content.clone();
+    match entries {
+        Some((first, last)) => {
+            for number in first..=last {
+                toggle_block(&mut toggled, Some(number))?;
+            }
+        }
+        None => toggle_block(&mut toggled, None)?,
     }
 
-    let a = content.rfind("\n\n");
-    let b = content.rfind("\n\t");
+    *content = toggled;
+    return Ok(());
+}
 
-    if a == None && b == None {
-        return;
+// Turns an entry into a line of the block before it, or into the start of a new block, which takes the
+// lines after it along. The lines nested under the entry move with it, so they stay nested under it.
+// The entries are counted from 1, and None is the last one.
+fn toggle_block(content: &mut String, number: Option<usize>) -> Result<(), String> {
+    let indent = config::current().indent;
+    let lines: Vec<&str> = content.split('\n').collect();
+    let levels = entry::levels(&lines);
+
+    // counted from the line after the header, the same way the main screen numbers them
+    let entries: Vec<usize> = (1..lines.len())
+        .filter(|i| entry::parse(lines[*i]).is_some())
+        .collect();
+
+    if entries.is_empty() {
+        return Err(String::from("Can't use '~' when there aren't any entries"));
     }
 
-    if let Some(newlines) = a {
-        if let Some(nl_tab) = b {
-            if newlines < nl_tab {
-                nl_tab_to_newlines(content, nl_tab);
-            } else {
-                newlines_to_nl_tab(content, newlines);
+    let index = number.unwrap_or(entries.len()).wrapping_sub(1);
+    let line = match entries.get(index) {
+        Some(line) => *line,
+        None => {
+            return Err(format!(
+                "There is no entry {} today, the entries go from 1 to {}",
+                number.unwrap_or(0),
+                entries.len()
+            ))
+        }
+    };
+
+    let level = levels[line];
+    if level == 0 && index == 0 {
+        return Err(String::from(
+            "The first entry of the day can't be added to a block before it",
+        ));
+    }
+
+    // the entry and everything nested under it, which ends at the next line that isn't deeper
+    let end = (line + 1..lines.len())
+        .find(|i| !lines[*i].trim().is_empty() && levels[*i] <= level)
+        .unwrap_or(lines.len());
+
+    let mut moved: Vec<String> = Vec::new();
+    for moved_line in &lines[line..end] {
+        let entry_level = match entry::parse(moved_line) {
+            Some(entry) => entry.level,
+            // text without a timestamp is left the way it was written
+            None => {
+                moved.push(String::from(*moved_line));
+                continue;
             }
+        };
+
+        let new_level = if level > 0 {
+            entry_level - level
         } else {
-            newlines_to_nl_tab(content, newlines);
-        }
-    } else {
-        if let Some(nl_tab) = b {
-            nl_tab_to_newlines(content, nl_tab);
-        }
+            entry_level + 1
+        };
+        let indent_len = entry::indentation(moved_line, &indent).1;
+        moved.push(format!(
+            "{}{}",
+            indent.repeat(new_level),
+            &moved_line[indent_len..]
+        ));
+    }
+
+    let before = lines[..line].join("\n");
+    let separator = if level > 0 { "\n\n" } else { "\n" };
+    let mut toggled = format!(
+        "{}{}{}",
+        before.trim_end_matches('\n'),
+        separator,
+        moved.join("\n")
+    );
+
+    if end < lines.len() {
+        toggled.push('\n');
+        toggled.push_str(&lines[end..].join("\n"));
+    }
+
+    *content = toggled;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folders(names: &[&str]) -> Vec<OsString> {
+        names.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn parse_time_reads_both_timestamp_formats() {
+        let date: DateTime<FixedOffset> = Local.ymd(2022, 5, 21).and_hms(12, 0, 0).into();
+        assert_eq!(
+            parse_time("\t09:50 pm - hi", &date),
+            Some(Local.ymd(2022, 5, 21).and_hms(21, 50, 0).into())
+        );
+        assert_eq!(
+            parse_time("12:05 am - hi", &date),
+            Some(Local.ymd(2022, 5, 21).and_hms(0, 5, 0).into())
+        );
+
+        let iso: DateTime<FixedOffset> = Local.ymd(2022, 5, 22).and_hms(1, 2, 3).into();
+        let line = format!(
+            "\t{} - past midnight",
+            iso.to_rfc3339_opts(SecondsFormat::Secs, false)
+        );
+        assert_eq!(parse_time(&line, &date), Some(iso));
+        assert_eq!(timestamp_len(&line[1..]), Some(25));
+        assert_eq!(timestamp_len("09:50 am - hi"), Some(8));
+        assert_eq!(timestamp_len("hello there"), None);
+    }
+
+    #[test]
+    fn journal_times_keep_the_journal_offset() {
+        let sydney = FixedOffset::east_opt(10 * 3600);
+        let wall_time = |h, min| NaiveDate::from_ymd(2022, 10, 2).and_hms(h, min, 0);
+
+        // 2:30 am doesn't exist in Sydney on 2022/10/2, and 3 am comes an hour after 1 am there,
+        // but a journal set to +10:00 counts its own hours whatever this computer's timezone does
+        let start = journal_time_in(wall_time(1, 30), sydney);
+        let skipped = journal_time_in(wall_time(2, 30), sydney);
+        let end = journal_time_in(wall_time(3, 30), sydney);
+        assert_eq!(end - start, Duration::hours(2));
+        assert_eq!(skipped.naive_local(), wall_time(2, 30));
+
+        let utc = Utc.ymd(2022, 10, 1).and_hms(16, 0, 0);
+        let time = to_journal_time_in(&utc, sydney);
+        assert_eq!(time.naive_local(), wall_time(2, 0));
+        assert_eq!(time, utc);
+    }
+
+    #[test]
+    fn split_placement_only_reads_one_marker() {
+        assert_eq!(split_placement("--deeper"), (Placement::Deeper, "deeper"));
+        assert_eq!(split_placement(">> deeper"), (Placement::Deeper, "deeper"));
+        assert_eq!(
+            split_placement("<< back up"),
+            (Placement::Shallower, "back up")
+        );
+        assert_eq!(
+            split_placement("-- -dashes"),
+            (Placement::Deeper, " -dashes")
+        );
+        assert_eq!(split_placement("> quoted"), (Placement::Same, "> quoted"));
+        assert_eq!(split_placement("<3 this"), (Placement::Same, "<3 this"));
+        assert_eq!(split_placement(">>= bind"), (Placement::Same, ">>= bind"));
+    }
+
+    #[test]
+    fn toggle_block_merges_and_splits_blocks() {
+        let mut text = String::from("Work - Saturday 2022/5/21\n\n\n09:00 am - a\n  09:10 am - b");
+        assert!(toggle_block(&mut text, None).is_ok());
+        assert_eq!(
+            text,
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\n09:10 am - b"
+        );
+        assert!(toggle_block(&mut text, None).is_ok());
+        assert_eq!(
+            text,
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\t09:10 am - b"
+        );
+
+        let mut text = String::from(
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\t09:10 am - b\n\t09:20 am - c\n\n09:30 am - d",
+        );
+        assert!(toggle_blocks(&mut text, parse_toggle("2").unwrap()).is_ok());
+        assert_eq!(
+            text,
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\n09:10 am - b\n\t09:20 am - c\n\n09:30 am - d"
+        );
+        assert!(toggle_blocks(&mut text, parse_toggle("4-2").unwrap()).is_ok());
+        assert_eq!(
+            text,
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\t09:10 am - b\n\n09:20 am - c\n\t09:30 am - d"
+        );
+
+        // nothing changes when part of a range can't be toggled
+        let before = text.clone();
+        assert!(toggle_blocks(&mut text, parse_toggle("1-2").unwrap()).is_err());
+        assert!(toggle_blocks(&mut text, parse_toggle("4-5").unwrap()).is_err());
+        assert_eq!(text, before);
+        assert_eq!(parse_toggle(" approximately"), None);
+    }
+
+    #[test]
+    fn toggle_block_moves_nested_lines_along() {
+        let nested = "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\t09:10 am - b\n\t\t09:20 am - c\n\t\t\t09:30 am - d\n\t09:40 am - e";
+        let mut text = String::from(nested);
+
+        assert!(toggle_blocks(&mut text, parse_toggle("2").unwrap()).is_ok());
+        assert_eq!(
+            text,
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\n09:10 am - b\n\t09:20 am - c\n\t\t09:30 am - d\n\t09:40 am - e"
+        );
+
+        // e is part of b's block now, so it stays under b when the block is added back to a
+        assert!(toggle_blocks(&mut text, parse_toggle("2").unwrap()).is_ok());
+        assert_eq!(
+            text,
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\t09:10 am - b\n\t\t09:20 am - c\n\t\t\t09:30 am - d\n\t\t09:40 am - e"
+        );
+
+        // only what is nested under c goes with it
+        let mut text = String::from(nested);
+        assert!(toggle_blocks(&mut text, parse_toggle("3").unwrap()).is_ok());
+        assert_eq!(
+            text,
+            "Work - Saturday 2022/5/21\n\n\n09:00 am - a\n\t09:10 am - b\n\n09:20 am - c\n\t09:30 am - d\n\t09:40 am - e"
+        );
+    }
+
+    #[test]
+    fn find_journal_prefers_exact_matches() {
+        let journals = folders(&["personal", "work", "workout"]);
+        assert_eq!(find_journal("Work", &journals), folders(&["work"]));
+        assert_eq!(find_journal("worko", &journals), folders(&["workout"]));
+        assert_eq!(find_journal("prs", &journals), folders(&["personal"]));
+        assert_eq!(find_journal("2", &journals), folders(&["workout"]));
+        assert_eq!(find_journal("wo", &journals), folders(&["work", "workout"]));
+        assert!(find_journal("xyz", &journals).is_empty());
+    }
+
+    #[test]
+    fn parse_years_sorts_and_ignores_non_years() {
+        let years = parse_years(&folders(&["2023", "notes", "2021", ".git", "2022", "20x1"]));
+        assert_eq!(years, vec![2021, 2022, 2023]);
+    }
+
+    #[test]
+    fn years_to_visit_from_within_range() {
+        let years = [2019, 2021, 2022];
+        assert_eq!(
+            years_to_visit(&years, 2021, Direction::Backwards),
+            vec![2021, 2019]
+        );
+        assert_eq!(
+            years_to_visit(&years, 2021, Direction::Forwards),
+            vec![2021, 2022]
+        );
+    }
+
+    #[test]
+    fn years_to_visit_across_gaps() {
+        let years = [2019, 2022];
+        assert_eq!(
+            years_to_visit(&years, 2020, Direction::Backwards),
+            vec![2019]
+        );
+        assert_eq!(
+            years_to_visit(&years, 2020, Direction::Forwards),
+            vec![2022]
+        );
+    }
+
+    #[test]
+    fn years_to_visit_outside_range() {
+        let years = [2020, 2021];
+        assert_eq!(
+            years_to_visit(&years, 2019, Direction::Backwards),
+            Vec::<i32>::new()
+        );
+        assert_eq!(
+            years_to_visit(&years, 2019, Direction::Forwards),
+            vec![2020, 2021]
+        );
+        assert_eq!(
+            years_to_visit(&years, 2025, Direction::Backwards),
+            vec![2021, 2020]
+        );
+        assert_eq!(
+            years_to_visit(&years, 2025, Direction::Forwards),
+            Vec::<i32>::new()
+        );
+        assert_eq!(
+            years_to_visit(&[], 2025, Direction::Backwards),
+            Vec::<i32>::new()
+        );
     }
 }
@@ -0,0 +1,316 @@
+use crate::{
+    config, crypto, entry, get_all_journals, get_input_str, journal_days, journal_display_name,
+    journal_root_dir, load_journal_err, now, parse_time, save_journal, validate_journal_name,
+};
+use chrono::{DateTime, FixedOffset};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+// Deleted journals are moved here instead of being removed, in case it was a mistake.
+const TRASH_DIR_NAME: &str = ".trash";
+
+// An archived journal has this file in its root folder.
+const ARCHIVED_FILE_NAME: &str = ".archived";
+
+fn archived_file_path(name: &OsStr) -> PathBuf {
+    let mut path = journal_root_dir(name);
+    path.push(ARCHIVED_FILE_NAME);
+
+    return path;
+}
+
+pub fn is_archived(name: &OsStr) -> bool {
+    return archived_file_path(name).is_file();
+}
+
+// Finds a journal by its whole name, ignoring case. Destructive commands use this rather
+// than find_journal, so that a typo can't pick some other journal.
+pub fn find_journal_exact(input: &str) -> Option<OsString> {
+    let input = input.trim().to_lowercase();
+    let journals = get_all_journals().ok()?;
+
+    return journals
+        .into_iter()
+        .find(|journal| journal.to_string_lossy().to_lowercase() == input);
+}
+
+fn move_to_trash(name: &OsStr) -> Result<PathBuf, String> {
+    let mut trash = PathBuf::from(config::current().journals_dir);
+    trash.push(TRASH_DIR_NAME);
+    fs::create_dir_all(&trash).map_err(|e| format!("Couldn't make the trash folder: {}", e))?;
+
+    // the same name could have been deleted before
+    trash.push(format!(
+        "{} {}",
+        name.to_string_lossy(),
+        now().format("%Y-%m-%d %H-%M-%S")
+    ));
+
+    fs::rename(journal_root_dir(name), &trash)
+        .map_err(|e| format!("Couldn't move the journal to the trash: {}", e))?;
+
+    return Ok(trash);
+}
+
+// The header of each day starts with the journal's name, so it has to change with the name.
+fn replace_header_name(text: &str, display_name: &str) -> String {
+    let (header, rest) = text.split_once('\n').unwrap_or((text, ""));
+    return match header.rsplit_once(" - ") {
+        Some((_, day)) => format!("{} - {}\n{}", display_name, day, rest),
+        None => String::from(text),
+    };
+}
+
+// Returns the name of the journal's new folder, along with a message.
+pub fn rename(name: &OsStr, new_name: &str) -> Result<(OsString, String), String> {
+    if new_name.trim().is_empty() {
+        return Err(String::from("Type the new name after /rename."));
+    }
+
+    let (new_name, display_name) = validate_journal_name(new_name, Some(name))?;
+    fs::rename(journal_root_dir(name), journal_root_dir(&new_name))
+        .map_err(|e| format!("Couldn't rename the journal: {}", e))?;
+    crypto::renamed(name, &new_name);
+
+    for date in journal_days(&new_name) {
+        if let Ok(text) = load_journal_err(&new_name, &date) {
+            save_journal(&new_name, &date, &replace_header_name(&text, &display_name));
+        }
+    }
+
+    let message = format!(
+        "[{}] was renamed to [{}].",
+        name.to_string_lossy(),
+        display_name
+    );
+
+    return Ok((new_name, message));
+}
+
+pub fn delete(name: &OsStr) -> Result<String, String> {
+    println!(
+        "This will move [{}] to the trash. Type its name to confirm, or anything else to cancel:",
+        name.to_string_lossy()
+    );
+
+    if get_input_str().trim() != name.to_string_lossy() {
+        return Err(String::from("Nothing was deleted."));
+    }
+
+    let trash = move_to_trash(name)?;
+    crypto::lock(name);
+
+    return Ok(format!(
+        "[{}] was moved to {:#?}.",
+        name.to_string_lossy(),
+        trash
+    ));
+}
+
+pub fn archive(name: &OsStr) -> Result<String, String> {
+    fs::write(archived_file_path(name), "")
+        .map_err(|e| format!("Couldn't archive the journal: {}", e))?;
+
+    return Ok(format!(
+        "[{}] was archived. It won't be shown when picking a journal, but '/switch {}' still opens it.",
+        name.to_string_lossy(),
+        name.to_string_lossy()
+    ));
+}
+
+pub fn unarchive(name: &OsStr) -> Result<String, String> {
+    if !is_archived(name) {
+        return Err(format!("[{}] isn't archived.", name.to_string_lossy()));
+    }
+
+    fs::remove_file(archived_file_path(name))
+        .map_err(|e| format!("Couldn't unarchive the journal: {}", e))?;
+
+    return Ok(format!(
+        "[{}] is no longer archived.",
+        name.to_string_lossy()
+    ));
+}
+
+// A block of a day, with the time it starts at.
+pub type TimedBlock<'a> = (Option<DateTime<FixedOffset>>, &'a str);
+
+// The header of a day, and its blocks with the time each one starts at.
+// Blocks without a timestamp are given the time of the block before them.
+pub fn timed_blocks<'a>(
+    text: &'a str,
+    date: &DateTime<FixedOffset>,
+) -> (&'a str, Vec<TimedBlock<'a>>) {
+    let (header, blocks) = entry::blocks(text);
+
+    let mut timed = Vec::new();
+    let mut last_time = None;
+    for block in blocks {
+        let time = parse_time(block, date).or(last_time);
+        last_time = time;
+        timed.push((time, block));
+    }
+
+    return (header, timed);
+}
+
+// Puts a day back together from its header and blocks.
+fn join_blocks(header: &str, blocks: &[TimedBlock]) -> String {
+    let mut text = String::from(header);
+    if blocks.is_empty() {
+        text.push('\n');
+    }
+    for (_, block) in blocks {
+        text.push_str("\n\n");
+        text.push_str(block);
+    }
+
+    return text;
+}
+
+// Blocks of `from_blocks` that are already in the day, at the same time, are left out, so merging or
+// importing the same thing twice doesn't add it twice. Returns the merged day and how many blocks were added.
+fn merge_timed_blocks<'a>(
+    header: &str,
+    mut blocks: Vec<TimedBlock<'a>>,
+    from_blocks: Vec<TimedBlock<'a>>,
+) -> (String, usize) {
+    let existing = blocks.len();
+    for (time, block) in from_blocks {
+        let already_there = blocks[..existing]
+            .iter()
+            .any(|(other_time, other)| *other_time == time && other.trim_end() == block.trim_end());
+        if !already_there {
+            blocks.push((time, block));
+        }
+    }
+    let added = blocks.len() - existing;
+
+    // stable, so blocks at the same time keep the journal being merged into first
+    blocks.sort_by_key(|(time, _)| *time);
+
+    return (join_blocks(header, &blocks), added);
+}
+
+// Puts the blocks of two versions of the same day in order of their first timestamp.
+// Blocks without a timestamp stay right after the block before them.
+pub fn merge_day(
+    into_text: &str,
+    from_text: &str,
+    date: &DateTime<FixedOffset>,
+) -> (String, usize) {
+    let (header, into_blocks) = timed_blocks(into_text, date);
+    let (_, from_blocks) = timed_blocks(from_text, date);
+
+    return merge_timed_blocks(header, into_blocks, from_blocks);
+}
+
+// Moves every block of `from` into `into`, day by day, and then moves `from` to the trash.
+pub fn merge(into: &OsStr, from: &OsStr) -> Result<String, String> {
+    if into == from {
+        return Err(String::from("Can't merge a journal into itself."));
+    }
+
+    if !crypto::unlock(into) || !crypto::unlock(from) {
+        return Err(String::from(
+            "Both journals need to be unlocked to merge them.",
+        ));
+    }
+
+    let display_name = journal_display_name(into);
+    let mut merged_days = 0;
+    for date in journal_days(from) {
+        let from_text = load_journal_err(from, &date)
+            .map_err(|e| format!("Couldn't read [{}]: {}", from.to_string_lossy(), e))?;
+
+        // the lines are read with the settings of `from`, and laid out again the way `into` is set up
+        let (from_header, from_blocks) = config::with_journal(from, || {
+            let (header, blocks) = timed_blocks(&from_text, &date);
+            let blocks: Vec<_> = blocks
+                .into_iter()
+                .map(|(time, block)| {
+                    let lines: Vec<_> = block.split('\n').map(entry::split_line).collect();
+                    return (time, lines);
+                })
+                .collect();
+
+            return (header, blocks);
+        });
+        let from_blocks: Vec<(Option<DateTime<FixedOffset>>, String)> =
+            config::with_journal(into, || {
+                return from_blocks
+                    .iter()
+                    .map(|(time, lines)| {
+                        let block: Vec<String> = lines
+                            .iter()
+                            .map(|(level, stamp, text)| entry::format_line(*level, *stamp, text))
+                            .collect();
+                        return (*time, block.join("\n"));
+                    })
+                    .collect();
+            });
+        let from_blocks: Vec<TimedBlock> = from_blocks
+            .iter()
+            .map(|(time, block)| (*time, block.as_str()))
+            .collect();
+
+        let merged = match load_journal_err(into, &date) {
+            Ok(into_text) => {
+                let (header, into_blocks) =
+                    config::with_journal(into, || timed_blocks(&into_text, &date));
+                merge_timed_blocks(header, into_blocks, from_blocks).0
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let header = replace_header_name(from_header, &display_name);
+                join_blocks(header.trim_end(), &from_blocks)
+            }
+            // anything else would mean writing over a day that is still there
+            Err(e) => {
+                return Err(format!(
+                    "Couldn't read [{}], stopped after merging {} days: {}",
+                    into.to_string_lossy(),
+                    merged_days,
+                    e
+                ));
+            }
+        };
+
+        save_journal(into, &date, &merged);
+        merged_days += 1;
+    }
+
+    let trash = move_to_trash(from)?;
+    crypto::lock(from);
+
+    return Ok(format!(
+        "Merged {} days of [{}] into [{}]. The old journal was moved to {:#?}.",
+        merged_days,
+        from.to_string_lossy(),
+        into.to_string_lossy(),
+        trash
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn merge_day_only_adds_blocks_once() {
+        let date: DateTime<FixedOffset> = Local.ymd(2022, 5, 21).and_hms(12, 0, 0).into();
+        let into = "work - Saturday 2022/5/21\n\n09:00 am - standup\n\n11:00 am - review";
+        let from = "home - Saturday 2022/5/21\n\n10:00 am - call\n\n11:00 am - review";
+
+        let (merged, added) = merge_day(into, from, &date);
+        assert_eq!(added, 1);
+        assert_eq!(
+            merged,
+            "work - Saturday 2022/5/21\n\n09:00 am - standup\n\n10:00 am - call\n\n11:00 am - review"
+        );
+
+        assert_eq!(merge_day(&merged, from, &date), (merged.clone(), 0));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::render;
+use crate::{datestamp, iterate_journals_dir, wait_for_input, Direction};
+use chrono::{DateTime, Duration, FixedOffset};
+use console::{Key, Term};
+use std::ffi::OsStr;
+
+// How many more days get loaded whenever we scroll past the oldest day we have.
+const DAYS_PER_LOAD: usize = 10;
+
+struct Day {
+    date: DateTime<FixedOffset>,
+    text: String,
+}
+
+// Loads up to `count` days with entries, starting at `date` and going backwards.
+// The result is in chronological order.
+fn load_days_before(name: &OsStr, date: &DateTime<FixedOffset>, count: usize) -> Vec<Day> {
+    let mut days: Vec<Day> = Vec::new();
+
+    iterate_journals_dir(name, date, Direction::Backwards, |current_date, text| {
+        days.push(Day {
+            date: *current_date,
+            text,
+        });
+
+        return days.len() < count;
+    });
+
+    days.reverse();
+    return days;
+}
+
+struct PrevPager<'a> {
+    name: &'a OsStr,
+    width: usize,
+    page_size: usize,
+
+    days: Vec<Day>,
+    // every day flattened into screen lines, and where each day starts in that list
+    lines: Vec<String>,
+    day_starts: Vec<usize>,
+
+    scroll: usize,
+    no_older_days: bool,
+}
+
+impl<'a> PrevPager<'a> {
+    fn new(name: &'a OsStr, date: &DateTime<FixedOffset>, width: usize, page_size: usize) -> Self {
+        let days = load_days_before(name, date, DAYS_PER_LOAD);
+        let no_older_days = days.len() < DAYS_PER_LOAD;
+
+        let mut pager = PrevPager {
+            name,
+            width,
+            page_size,
+            days,
+            lines: Vec::new(),
+            day_starts: Vec::new(),
+            scroll: 0,
+            no_older_days,
+        };
+
+        pager.rebuild_lines();
+        pager.scroll = pager.day_starts.last().copied().unwrap_or(0);
+
+        return pager;
+    }
+
+    fn rebuild_lines(&mut self) {
+        self.lines.clear();
+        self.day_starts.clear();
+
+        for day in &self.days {
+            self.day_starts.push(self.lines.len());
+            self.lines.push(render::dim(&format!(
+                "---------------- <{}> ----------------",
+                datestamp(&day.date)
+            )));
+            self.lines.push(String::new());
+            self.lines
+                .append(&mut render::render_day(&day.text, None, self.width, false));
+
+            self.lines.push(String::new());
+        }
+    }
+
+    // Returns true if any older days were loaded. The scroll position is kept on the same line.
+    fn load_older_days(&mut self) -> bool {
+        if self.no_older_days {
+            return false;
+        }
+
+        let oldest = match self.days.first() {
+            Some(day) => day.date - Duration::days(1),
+            None => return false,
+        };
+
+        let mut older = load_days_before(self.name, &oldest, DAYS_PER_LOAD);
+        if older.len() < DAYS_PER_LOAD {
+            self.no_older_days = true;
+        }
+
+        if older.is_empty() {
+            return false;
+        }
+
+        let old_len = self.lines.len();
+        older.append(&mut self.days);
+        self.days = older;
+        self.rebuild_lines();
+        self.scroll += self.lines.len() - old_len;
+
+        return true;
+    }
+
+    fn max_scroll(&self) -> usize {
+        let last_day = self.day_starts.last().copied().unwrap_or(0);
+        return last_day.max(self.lines.len().saturating_sub(self.page_size));
+    }
+
+    fn current_day(&self) -> usize {
+        return self
+            .day_starts
+            .iter()
+            .rposition(|start| *start <= self.scroll)
+            .unwrap_or(0);
+    }
+
+    fn scroll_up(&mut self, amount: usize) {
+        while self.scroll < amount && self.load_older_days() {}
+
+        self.scroll = self.scroll.saturating_sub(amount);
+    }
+
+    fn scroll_down(&mut self, amount: usize) {
+        self.scroll = (self.scroll + amount).min(self.max_scroll());
+    }
+
+    fn previous_day(&mut self) {
+        if self.day_starts.is_empty() {
+            return;
+        }
+
+        let mut day = self.current_day();
+        if self.scroll > self.day_starts[day] {
+            self.scroll = self.day_starts[day];
+            return;
+        }
+
+        if day == 0 {
+            if !self.load_older_days() {
+                return;
+            }
+
+            day = self.current_day();
+        }
+
+        if day > 0 {
+            self.scroll = self.day_starts[day - 1];
+        }
+    }
+
+    fn next_day(&mut self) {
+        let day = self.current_day();
+        if let Some(start) = self.day_starts.get(day + 1) {
+            self.scroll = (*start).min(self.max_scroll());
+        }
+    }
+
+    fn render(&self, term: &Term) {
+        let _ = term.clear_screen();
+
+        let end = (self.scroll + self.page_size).min(self.lines.len());
+        for line in &self.lines[self.scroll..end] {
+            let _ = term.write_line(line);
+        }
+
+        for _ in end..(self.scroll + self.page_size) {
+            let _ = term.write_line("");
+        }
+
+        let status = match self.days.get(self.current_day()) {
+            Some(day) => format!(
+                "[{} - day {}/{}{}]",
+                datestamp(&day.date),
+                self.current_day() + 1,
+                self.days.len(),
+                if self.no_older_days { "" } else { "+" }
+            ),
+            None => String::from("[no entries]"),
+        };
+
+        let _ = term.write_line(&format!(
+            "{}  up/down: scroll, pgup/pgdn: page, left/right: day, home/end: oldest/latest, q: back",
+            status
+        ));
+    }
+}
+
+pub fn display_prev_journals(name: &OsStr, date: &DateTime<FixedOffset>, page_size: usize) {
+    let term = Term::stdout();
+    let (height, width) = term.size();
+
+    // leave a line for the status bar
+    let page_size = page_size.min((height as usize).saturating_sub(1)).max(1);
+    let mut pager = PrevPager::new(name, date, width as usize, page_size);
+
+    if !term.is_term() {
+        // can't read keys, so just print the latest page and let the caller move on
+        pager.render(&term);
+        return;
+    }
+
+    let _ = term.hide_cursor();
+    loop {
+        pager.render(&term);
+
+        if !wait_for_input() {
+            break;
+        }
+
+        let key = match term.read_key() {
+            Ok(key) => key,
+            Err(_) => break,
+        };
+
+        match key {
+            Key::ArrowUp | Key::Char('k') => pager.scroll_up(1),
+            Key::ArrowDown | Key::Char('j') => pager.scroll_down(1),
+            Key::PageUp | Key::Char('b') => pager.scroll_up(pager.page_size),
+            Key::PageDown | Key::Char(' ') => pager.scroll_down(pager.page_size),
+            Key::ArrowLeft | Key::Char('p') => pager.previous_day(),
+            Key::ArrowRight | Key::Char('n') => pager.next_day(),
+            Key::Home | Key::Char('g') => {
+                while pager.load_older_days() {}
+                pager.scroll = 0;
+            }
+            Key::End | Key::Char('G') => {
+                pager.scroll = pager.day_starts.last().copied().unwrap_or(0);
+            }
+            Key::Char('q') | Key::Escape | Key::Enter => break,
+            _ => {}
+        }
+    }
+    let _ = term.show_cursor();
+}
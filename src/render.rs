@@ -0,0 +1,202 @@
+use crate::todo;
+use crate::{entry, find_tags};
+use console::{style, Style};
+
+#[derive(Copy, Clone, PartialEq)]
+enum Span {
+    Plain,
+    Header,
+    Timestamp,
+    BlockStart,
+    Tag,
+    Url,
+    OpenTask,
+    DoneTask,
+    SearchHit,
+}
+
+fn span_style(span: Span) -> Style {
+    return match span {
+        Span::Plain => Style::new(),
+        Span::Header => Style::new().bold(),
+        Span::Timestamp => Style::new().dim(),
+        Span::BlockStart => Style::new().cyan().bold(),
+        Span::Tag => Style::new().yellow(),
+        Span::Url => Style::new().blue().underlined(),
+        Span::OpenTask => Style::new().magenta(),
+        Span::DoneTask => Style::new().green().dim(),
+        Span::SearchHit => Style::new().black().on_yellow(),
+    };
+}
+
+fn mark(spans: &mut [Span], start: usize, end: usize, span: Span) {
+    let end = end.min(spans.len());
+    for s in spans[start..end].iter_mut() {
+        *s = span;
+    }
+}
+
+fn classify(chars: &[char], is_header: bool, search: Option<&str>) -> Vec<Span> {
+    let mut spans = vec![Span::Plain; chars.len()];
+
+    let text: String = chars.iter().collect();
+    let char_index = |byte_index: usize| text[..byte_index].chars().count();
+
+    if is_header {
+        mark(&mut spans, 0, chars.len(), Span::Header);
+    } else if let Some(entry) = entry::parse(&text) {
+        let stamp_start = char_index(entry.stamp_start);
+        let stamp_end = stamp_start + entry.stamp.len();
+        mark(&mut spans, stamp_start, stamp_end, Span::Timestamp);
+
+        if entry.level == 0 {
+            mark(&mut spans, stamp_end, chars.len(), Span::BlockStart);
+        }
+
+        if let Some((done, _)) = todo::parse_task(&text) {
+            let span = if done { Span::DoneTask } else { Span::OpenTask };
+            mark(
+                &mut spans,
+                char_index(entry.contents_start),
+                chars.len(),
+                span,
+            );
+        }
+    }
+
+    for (start, tag) in find_tags(&text) {
+        let start = char_index(start);
+        mark(&mut spans, start, start + tag.chars().count(), Span::Tag);
+    }
+
+    let mut word_start = 0;
+    for word in text.split(' ') {
+        if word.starts_with("http://") || word.starts_with("https://") {
+            let start = char_index(word_start);
+            mark(&mut spans, start, start + word.chars().count(), Span::Url);
+        }
+
+        word_start += word.len() + 1;
+    }
+
+    if let Some(search) = search {
+        let search: Vec<char> = search.chars().map(|c| c.to_ascii_lowercase()).collect();
+        if !search.is_empty() && search.len() <= chars.len() {
+            for start in 0..=(chars.len() - search.len()) {
+                let is_hit = chars[start..start + search.len()]
+                    .iter()
+                    .zip(search.iter())
+                    .all(|(a, b)| a.to_ascii_lowercase() == *b);
+
+                if is_hit {
+                    mark(&mut spans, start, start + search.len(), Span::SearchHit);
+                }
+            }
+        }
+    }
+
+    return spans;
+}
+
+fn paint(chars: &[char], spans: &[Span]) -> String {
+    let mut painted = String::new();
+    let mut start = 0;
+
+    while start < chars.len() {
+        let span = spans[start];
+        let mut end = start;
+        while end < chars.len() && spans[end] == span {
+            end += 1;
+        }
+
+        let run: String = chars[start..end].iter().collect();
+        if span == Span::Plain {
+            painted.push_str(&run);
+        } else {
+            painted.push_str(&format!("{}", span_style(span).apply_to(run)));
+        }
+
+        start = end;
+    }
+
+    return painted;
+}
+
+// Renders one line of a journal, split into screen lines no wider than `width` (0 to not wrap).
+// Tabs are expanded when wrapping, since we need to know how wide they are.
+// Styling is left out automatically when stdout isn't a terminal.
+pub fn render_line(line: &str, is_header: bool, search: Option<&str>, width: usize) -> Vec<String> {
+    let chars: Vec<char> = if width == 0 {
+        line.chars().collect()
+    } else {
+        line.replace('\t', "    ").chars().collect()
+    };
+    let spans = classify(&chars, is_header, search);
+
+    if chars.is_empty() || width == 0 {
+        return vec![paint(&chars, &spans)];
+    }
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + width).min(chars.len());
+        lines.push(paint(&chars[start..end], &spans[start..end]));
+        start = end;
+    }
+
+    return lines;
+}
+
+// Renders a whole day's journal text, where the first line is the header. When `numbered`, the entries
+// are numbered down the left side, the same way '~3' counts them.
+pub fn render_day(text: &str, search: Option<&str>, width: usize, numbered: bool) -> Vec<String> {
+    let is_entry: Vec<bool> = text
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| i > 0 && entry::parse(line).is_some())
+        .collect();
+
+    let entry_count = is_entry.iter().filter(|is_entry| **is_entry).count();
+    let gutter = if numbered && entry_count > 0 {
+        entry_count.to_string().len() + 1
+    } else {
+        0
+    };
+    let width = if width == 0 {
+        0
+    } else {
+        width.saturating_sub(gutter).max(1)
+    };
+
+    let mut lines = Vec::new();
+    let mut number = 0;
+    for (i, line) in text.split('\n').enumerate() {
+        let rendered = render_line(line, i == 0, search, width);
+        if gutter == 0 {
+            lines.extend(rendered);
+            continue;
+        }
+
+        for (j, screen_line) in rendered.into_iter().enumerate() {
+            let label = if j == 0 && is_entry[i] {
+                number += 1;
+                number.to_string()
+            } else {
+                String::new()
+            };
+
+            lines.push(format!(
+                "{}{}",
+                dim(&format!("{:>1$} ", label, gutter - 1)),
+                screen_line
+            ));
+        }
+    }
+
+    return lines;
+}
+
+pub fn dim(text: &str) -> String {
+    return format!("{}", style(text).dim());
+}
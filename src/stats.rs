@@ -0,0 +1,285 @@
+use crate::{
+    clear_screen, entry, get_input_str, iterate_journals_dir, journal_days, load_journal_err, now,
+    parse_time, render, Direction,
+};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Timelike};
+use std::ffi::OsStr;
+
+// Entries closer together than this are counted as the same session.
+const SESSION_GAP_MINUTES: i64 = 30;
+const HISTOGRAM_WIDTH: usize = 40;
+
+struct DayStats {
+    date: NaiveDate,
+    times: Vec<DateTime<FixedOffset>>,
+    words: usize,
+}
+
+// The timestamps and word count of the entries in a day.
+fn day_stats(date: &DateTime<FixedOffset>, journal_text: &str) -> DayStats {
+    let mut times = Vec::new();
+    let mut words = 0;
+
+    for line in journal_text.split('\n').skip(1) {
+        if let Some(time) = parse_time(line, date) {
+            times.push(time);
+
+            let contents = entry::contents(line).unwrap_or("");
+            words += contents.split_whitespace().count();
+        }
+    }
+
+    return DayStats {
+        date: date.date().naive_local(),
+        times,
+        words,
+    };
+}
+
+// The current streak, and the last day with entries. Only the days in the streak are read, but
+// they still have to be loaded (and decrypted), so this is worked out once when a journal is opened.
+pub fn recent_streak(name: &OsStr) -> (usize, Option<NaiveDate>) {
+    let today = now().date().naive_local();
+    let mut streak = 0;
+    let mut last_day: Option<NaiveDate> = None;
+    let mut expected: Option<NaiveDate> = None;
+
+    for date in journal_days(name).iter().rev() {
+        let day = date.date().naive_local();
+        if day > today {
+            continue;
+        }
+
+        match load_journal_err(name, date) {
+            Ok(text) if entry::has_entries(&text) => {}
+            _ => continue,
+        }
+
+        if last_day.is_none() {
+            last_day = Some(day);
+        }
+
+        // today doesn't need entries yet for the streak to still be going
+        let continues_streak = match expected {
+            None => today - day <= Duration::days(1),
+            Some(expected) => day == expected,
+        };
+        if !continues_streak {
+            break;
+        }
+
+        streak += 1;
+        expected = Some(day - Duration::days(1));
+    }
+
+    return (streak, last_day);
+}
+
+// The streak from recent_streak, counting today once it has entries. The streak is only worked out
+// when the journal is opened, so writing the first entry of the day wouldn't show up in it otherwise.
+pub fn streak_with_today(
+    (streak, last_day): (usize, Option<NaiveDate>),
+    today: NaiveDate,
+    today_has_entries: bool,
+) -> usize {
+    if !today_has_entries || last_day == Some(today) {
+        return streak;
+    }
+
+    if last_day == Some(today - Duration::days(1)) {
+        return streak + 1;
+    }
+
+    return 1;
+}
+
+// Returns the current and the longest streak of consecutive days, given days sorted oldest first.
+// The current streak still counts if today has no entries yet, as long as yesterday does.
+pub fn streaks(days: &[NaiveDate], today: NaiveDate) -> (usize, usize) {
+    let mut longest = 0;
+    let mut streak = 0;
+    let mut previous: Option<NaiveDate> = None;
+
+    for day in days {
+        streak = match previous {
+            Some(previous) if *day - previous == Duration::days(1) => streak + 1,
+            Some(previous) if *day == previous => streak,
+            _ => 1,
+        };
+        longest = longest.max(streak);
+        previous = Some(*day);
+    }
+
+    let current = match previous {
+        Some(last) if today - last <= Duration::days(1) => streak,
+        _ => 0,
+    };
+
+    return (current, longest);
+}
+
+// The total length of the sessions in a day, and how many there were.
+fn sessions(times: &[DateTime<FixedOffset>]) -> (Duration, usize) {
+    let mut total = Duration::zero();
+    let mut count = 0;
+    let mut session_start: Option<DateTime<FixedOffset>> = None;
+
+    for (i, time) in times.iter().enumerate() {
+        let start = *session_start.get_or_insert(*time);
+
+        let is_last = match times.get(i + 1) {
+            Some(next) => *next - *time > Duration::minutes(SESSION_GAP_MINUTES),
+            None => true,
+        };
+        if is_last {
+            total = total + (*time - start);
+            count += 1;
+            session_start = None;
+        }
+    }
+
+    return (total, count);
+}
+
+fn print_histogram(labels: &[String], counts: &[usize]) {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let label_width = labels.iter().map(|label| label.len()).max().unwrap_or(0);
+
+    for (label, count) in labels.iter().zip(counts.iter()) {
+        let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(max));
+        println!(
+            "    {:>width$} | {} {}",
+            label,
+            bar,
+            count,
+            width = label_width
+        );
+    }
+}
+
+pub fn display_stats(name: &OsStr, show_histograms: bool) {
+    clear_screen();
+    println!("Counting up [{}] ...", name.to_string_lossy());
+
+    let mut days: Vec<DayStats> = Vec::new();
+    iterate_journals_dir(name, &now(), Direction::Backwards, |date, journal_text| {
+        let stats = day_stats(date, &journal_text);
+        if !stats.times.is_empty() {
+            days.push(stats);
+        }
+
+        return true;
+    });
+    days.reverse();
+
+    clear_screen();
+    println!(
+        "{}\n",
+        render::render_line(
+            &format!("Stats for {}", name.to_string_lossy()),
+            true,
+            None,
+            0
+        )
+        .join("")
+    );
+
+    if days.is_empty() {
+        println!("There aren't any entries yet.");
+        println!("\n\npress enter to go back ...");
+        get_input_str();
+        return;
+    }
+
+    let dates: Vec<NaiveDate> = days.iter().map(|day| day.date).collect();
+    let (current_streak, longest_streak) = streaks(&dates, now().date().naive_local());
+
+    let entries: usize = days.iter().map(|day| day.times.len()).sum();
+    let words: usize = days.iter().map(|day| day.words).sum();
+
+    let mut session_time = Duration::zero();
+    let mut session_count = 0;
+    let mut hours = [0_usize; 24];
+    let mut weekdays = [0_usize; 7];
+    for day in &days {
+        let (time, count) = sessions(&day.times);
+        session_time = session_time + time;
+        session_count += count;
+
+        for time in &day.times {
+            hours[time.hour() as usize] += 1;
+        }
+        weekdays[day.date.weekday().num_days_from_monday() as usize] += day.times.len();
+    }
+
+    let mut busiest_hours: Vec<usize> = (0..24).filter(|hour| hours[*hour] > 0).collect();
+    busiest_hours.sort_by_key(|hour| std::cmp::Reverse(hours[*hour]));
+    let busiest_hours: Vec<String> = busiest_hours
+        .iter()
+        .take(3)
+        .map(|hour| format!("{:02}:00 ({} entries)", hour, hours[*hour]))
+        .collect();
+
+    let day_count = days.len();
+    println!(
+        "Days journaled:\t\t{} (first {}, last {})",
+        day_count,
+        dates[0],
+        dates[day_count - 1]
+    );
+    println!("Current streak:\t\t{} days", current_streak);
+    println!("Longest streak:\t\t{} days", longest_streak);
+    println!(
+        "Entries:\t\t{} ({:.1} per day)",
+        entries,
+        entries as f64 / day_count as f64
+    );
+    println!(
+        "Words:\t\t\t{} ({:.1} per day)",
+        words,
+        words as f64 / day_count as f64
+    );
+    println!("Most active hours:\t{}", busiest_hours.join(", "));
+    println!(
+        "Average session:\t{:.2}h ({} sessions, a new one starts after {} minutes without entries)",
+        session_time.num_minutes() as f64 / 60.0 / session_count as f64,
+        session_count,
+        SESSION_GAP_MINUTES
+    );
+
+    if show_histograms {
+        println!("\n\nEntries by hour of the day:\n");
+        let labels: Vec<String> = (0..24).map(|hour| format!("{:02}:00", hour)).collect();
+        print_histogram(&labels, &hours);
+
+        println!("\n\nEntries by day of the week:\n");
+        let labels: Vec<String> = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+            .iter()
+            .map(|day| String::from(*day))
+            .collect();
+        print_histogram(&labels, &weekdays);
+    } else {
+        println!("\nType /stats histogram to also see when the entries were made.");
+    }
+
+    println!("\n\npress enter to go back ...");
+    get_input_str();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: u32) -> NaiveDate {
+        return NaiveDate::from_ymd(2022, 5, day);
+    }
+
+    #[test]
+    fn streaks_count_consecutive_days() {
+        let days = [day(1), day(2), day(3), day(5), day(6)];
+        assert_eq!(streaks(&days, day(6)), (2, 3));
+        assert_eq!(streaks(&days, day(7)), (2, 3));
+        assert_eq!(streaks(&days, day(8)), (0, 3));
+        assert_eq!(streaks(&[], day(8)), (0, 0));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{add_input, journal_root_dir, push_block, split_placement};
+use chrono::{DateTime, Datelike, FixedOffset, Weekday};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+// Templates are text files in this folder of a journal. Each line is typed in as if it was entered
+// by hand, so lines starting with a dash (-) start new blocks.
+const TEMPLATES_DIR_NAME: &str = ".templates";
+
+// Filled into every new day, unless there is a template for that day of the week, like monday.txt.
+const DAY_TEMPLATE_NAME: &str = "day";
+
+fn templates_dir(name: &OsStr) -> PathBuf {
+    let mut path = journal_root_dir(name);
+    path.push(TEMPLATES_DIR_NAME);
+
+    return path;
+}
+
+fn template_path(name: &OsStr, template: &str) -> PathBuf {
+    let mut path = templates_dir(name);
+    path.push(format!("{}.txt", template));
+
+    return path;
+}
+
+// The template a name means, ignoring case. Only templates that are in the folder can be picked,
+// so a typed name like "../../work/2022/05/21" can't point anywhere else.
+fn find_template<'a>(templates: &'a [String], template: &str) -> Option<&'a str> {
+    let template = template.trim().to_lowercase();
+    return templates
+        .iter()
+        .find(|name| name.to_lowercase() == template)
+        .map(String::as_str);
+}
+
+fn weekday_name(date: &DateTime<FixedOffset>) -> &'static str {
+    return match date.weekday() {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    };
+}
+
+// The first line of a template always starts a new block, so it doesn't get added to whatever block came before.
+fn fill(template: &str, date: DateTime<FixedOffset>, content: &mut String) -> Result<(), String> {
+    let mut lines = template
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    if let Some(first) = lines.next() {
+        push_block(date, split_placement(first).1, content);
+    }
+
+    for line in lines {
+        add_input(date, line, content)?;
+    }
+
+    return Ok(());
+}
+
+// The names of the templates in a journal, without the .txt on the end.
+pub fn list(name: &OsStr) -> Vec<String> {
+    let entries = match templates_dir(name).read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut templates: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension() == Some(OsStr::new("txt")))
+        .filter_map(|path| Some(String::from(path.file_stem()?.to_str()?)))
+        .collect();
+
+    templates.sort();
+    return templates;
+}
+
+// Fills the template for a new day into its text, if the journal has one.
+pub fn fill_new_day(name: &OsStr, date: DateTime<FixedOffset>, content: &mut String) {
+    let templates = list(name);
+    for template in [weekday_name(&date), DAY_TEMPLATE_NAME].iter() {
+        let path = find_template(&templates, template).map(|found| template_path(name, found));
+        if let Some(Ok(text)) = path.map(fs::read_to_string) {
+            let _ = fill(&text, date, content);
+            return;
+        }
+    }
+}
+
+pub fn insert(
+    name: &OsStr,
+    template: &str,
+    date: DateTime<FixedOffset>,
+    content: &mut String,
+) -> Result<(), String> {
+    let templates = list(name);
+    if templates.is_empty() {
+        return Err(format!(
+            "There are no templates yet. Put them in {:#?} as .txt files.",
+            templates_dir(name)
+        ));
+    }
+
+    let found = find_template(&templates, template).ok_or_else(|| {
+        format!(
+            "There is no template called '{}'. The templates are: {}",
+            template,
+            templates.join(", ")
+        )
+    })?;
+
+    let path = template_path(name, found);
+    let text =
+        fs::read_to_string(&path).map_err(|e| format!("Couldn't read {:#?}: {}", path, e))?;
+
+    return fill(&text, date, content);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_template_ignores_case_and_only_picks_listed_ones() {
+        let templates = vec![String::from("Monday"), String::from("standup")];
+        assert_eq!(find_template(&templates, "monday"), Some("Monday"));
+        assert_eq!(find_template(&templates, "Standup"), Some("standup"));
+        assert_eq!(find_template(&templates, "../../personal/2022/05/21"), None);
+        assert_eq!(find_template(&templates, ""), None);
+    }
+}
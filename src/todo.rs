@@ -0,0 +1,182 @@
+use crate::{
+    add_input, clear_screen, entry, get_input_str, iterate_journals_dir, now, parse_time, Direction,
+};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+use std::ffi::OsStr;
+
+// Only this many of the most recent days with entries are looked at for open tasks.
+const TODO_SEARCH_DAYS: usize = 30;
+
+const OPEN_BOX: &str = "[ ]";
+const DONE_BOX: &str = "[x]";
+
+pub struct Task {
+    pub text: String,
+    pub first_seen: NaiveDate,
+}
+
+// Returns whether a line is a task that is done, and what the task is.
+pub fn parse_task(line: &str) -> Option<(bool, &str)> {
+    let contents = entry::contents(line)?;
+    let done = if contents.starts_with(OPEN_BOX) {
+        false
+    } else if contents.starts_with(DONE_BOX) || contents.starts_with("[X]") {
+        true
+    } else {
+        return None;
+    };
+
+    let text = contents[OPEN_BOX.len()..].trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    return Some((done, text));
+}
+
+// Goes through the recent days oldest first. A task stays open until a line with the same text is ticked off,
+// so tasks that were carried over to later days are only listed once.
+pub fn open_tasks(name: &OsStr, before: &DateTime<FixedOffset>) -> Vec<Task> {
+    let mut days: Vec<(DateTime<FixedOffset>, String)> = Vec::new();
+    iterate_journals_dir(name, before, Direction::Backwards, |date, journal_text| {
+        days.push((*date, journal_text));
+        return days.len() < TODO_SEARCH_DAYS;
+    });
+
+    let mut tasks: Vec<Task> = Vec::new();
+    for (date, journal_text) in days.iter().rev() {
+        for line in journal_text.split('\n').skip(1) {
+            if parse_time(line, date).is_none() {
+                continue;
+            }
+
+            match parse_task(line) {
+                Some((true, text)) => tasks.retain(|task| !task.text.eq_ignore_ascii_case(text)),
+                Some((false, text))
+                    if !tasks
+                        .iter()
+                        .any(|task| task.text.eq_ignore_ascii_case(text)) =>
+                {
+                    tasks.push(Task {
+                        text: String::from(text),
+                        first_seen: date.date().naive_local(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    return tasks;
+}
+
+pub fn display_open_tasks(name: &OsStr) {
+    clear_screen();
+
+    let tasks = open_tasks(name, &now());
+    if tasks.is_empty() {
+        println!(
+            "There are no open tasks in the last {} days of [{}].",
+            TODO_SEARCH_DAYS,
+            name.to_string_lossy()
+        );
+    } else {
+        println!("Open tasks in [{}]:\n", name.to_string_lossy());
+        for (i, task) in tasks.iter().enumerate() {
+            println!(
+                "[{}] - {} {}    (since {})",
+                i,
+                OPEN_BOX,
+                task.text,
+                task.first_seen.format("%Y/%-m/%-d")
+            );
+        }
+
+        println!("\nType /done followed by a number or the start of a task to tick it off.");
+    }
+
+    println!("\n\npress enter to go back ...");
+    get_input_str();
+}
+
+// Ticks off an open task by adding a line for it to today. Returns the task that was ticked off.
+pub fn mark_done(
+    name: &OsStr,
+    requested: &str,
+    date: DateTime<FixedOffset>,
+    content: &mut String,
+) -> Result<String, String> {
+    let requested = requested.trim();
+    if requested.is_empty() {
+        return Err(String::from(
+            "Type /done followed by the number of a task from /todo, or the start of it.",
+        ));
+    }
+
+    let tasks = open_tasks(name, &date);
+
+    let task = match requested.parse::<usize>() {
+        Ok(index) => tasks.get(index),
+        Err(_) => {
+            let requested = requested.to_lowercase();
+            let matches: Vec<&Task> = tasks
+                .iter()
+                .filter(|task| task.text.to_lowercase().starts_with(&requested))
+                .collect();
+
+            if matches.len() > 1 {
+                return Err(format!(
+                    "'{}' could be more than one task, type /todo to see their numbers.",
+                    requested
+                ));
+            }
+
+            matches.first().copied()
+        }
+    };
+
+    let task = match task {
+        Some(task) => task,
+        None => return Err(format!("There is no open task '{}'.", requested)),
+    };
+
+    add_input(date, &format!("{} {}", DONE_BOX, task.text), content)?;
+    return Ok(task.text.clone());
+}
+
+// Copies the tasks that are still open into a new day, so they aren't forgotten.
+pub fn carry_over(name: &OsStr, date: DateTime<FixedOffset>, content: &mut String) {
+    let tasks = open_tasks(name, &(date - Duration::days(1)));
+    if tasks.is_empty() {
+        return;
+    }
+
+    let _ = add_input(date, "-Unfinished tasks", content);
+    for task in tasks {
+        let _ = add_input(date, &format!("{} {}", OPEN_BOX, task.text), content);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_task_reads_checkboxes() {
+        assert_eq!(
+            parse_task("09:00 am - [ ] send report"),
+            Some((false, "send report"))
+        );
+        assert_eq!(
+            parse_task("\t09:00 am - [x] send report"),
+            Some((true, "send report"))
+        );
+        assert_eq!(
+            parse_task("\t09:00 am - [X] send report"),
+            Some((true, "send report"))
+        );
+        assert_eq!(parse_task("09:00 am - send report [ ]"), None);
+        assert_eq!(parse_task("09:00 am - [ ]"), None);
+        assert_eq!(parse_task("[ ] no timestamp"), None);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{add_input, entry, parse_time, timestamp};
+use chrono::{DateTime, Duration, FixedOffset};
+
+// The markers written by /start, /pause, /resume and /stop, followed by the name of the task.
+const START: &str = "[start]";
+const PAUSE: &str = "[pause]";
+const RESUME: &str = "[resume]";
+const STOP: &str = "[stop]";
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Marker {
+    Start,
+    Pause,
+    Resume,
+    Stop,
+}
+
+pub struct Interval {
+    pub task: String,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+fn parse_marker(line: &str) -> Option<(Marker, &str)> {
+    let contents = entry::contents(line)?;
+
+    for (text, marker) in [
+        (START, Marker::Start),
+        (PAUSE, Marker::Pause),
+        (RESUME, Marker::Resume),
+        (STOP, Marker::Stop),
+    ]
+    .iter()
+    {
+        if let Some(task) = contents.strip_prefix(text) {
+            return Some((*marker, task.trim()));
+        }
+    }
+
+    return None;
+}
+
+// The task that is being tracked at the end of the day, and whether it is paused.
+fn current_task(text: &str, date: &DateTime<FixedOffset>) -> Option<(String, bool)> {
+    let mut current: Option<(String, bool)> = None;
+
+    for line in text.split('\n').skip(1) {
+        if parse_time(line, date).is_none() {
+            continue;
+        }
+
+        current = match parse_marker(line) {
+            Some((Marker::Start, task)) | Some((Marker::Resume, task)) => {
+                Some((String::from(task), false))
+            }
+            Some((Marker::Pause, task)) => Some((String::from(task), true)),
+            Some((Marker::Stop, _)) => None,
+            None => current,
+        };
+    }
+
+    return current;
+}
+
+// The time spent on each task, from the markers in a day. A task that is still going ends at `until`.
+pub fn intervals(
+    text: &str,
+    date: &DateTime<FixedOffset>,
+    until: DateTime<FixedOffset>,
+) -> Vec<Interval> {
+    let mut intervals = Vec::new();
+    let mut running: Option<(String, DateTime<FixedOffset>)> = None;
+
+    for line in text.split('\n').skip(1) {
+        let time = match parse_time(line, date) {
+            Some(time) => time,
+            None => continue,
+        };
+
+        let marker = match parse_marker(line) {
+            Some(marker) => marker,
+            None => continue,
+        };
+
+        if let Some((task, start)) = running.take() {
+            intervals.push(Interval {
+                task,
+                start,
+                end: time,
+            });
+        }
+
+        if let (Marker::Start, task) | (Marker::Resume, task) = marker {
+            running = Some((String::from(task), time));
+        }
+    }
+
+    if let Some((task, start)) = running {
+        intervals.push(Interval {
+            task,
+            start,
+            end: until.max(start),
+        });
+    }
+
+    return intervals;
+}
+
+// Handles /start, /pause, /resume and /stop by adding a marker to the day's text.
+pub fn handle_command(
+    input: &str,
+    date: DateTime<FixedOffset>,
+    content: &mut String,
+) -> Result<String, String> {
+    let (command, task) = match input.split_once(' ') {
+        Some((command, task)) => (command, task.trim()),
+        None => (input, ""),
+    };
+
+    let current = current_task(content, &date);
+
+    match (command, current) {
+        ("/start", _) if task.is_empty() => {
+            return Err(String::from("Type the name of the task after /start."));
+        }
+        ("/start", current) => {
+            if let Some((current, false)) = current {
+                add_input(date, &format!("{} {}", STOP, current), content)?;
+            }
+
+            add_input(date, &format!("-{} {}", START, task), content)?;
+            return Ok(format!("Started '{}'.", task));
+        }
+        ("/pause", Some((current, false))) => {
+            add_input(date, &format!("{} {}", PAUSE, current), content)?;
+            return Ok(format!("Paused '{}'. Type /resume to carry on.", current));
+        }
+        ("/resume", Some((current, true))) => {
+            add_input(date, &format!("{} {}", RESUME, current), content)?;
+            return Ok(format!("Resumed '{}'.", current));
+        }
+        ("/stop", Some((current, _))) => {
+            add_input(date, &format!("{} {}", STOP, current), content)?;
+            return Ok(format!("Stopped '{}'.", current));
+        }
+        ("/pause", _) | ("/stop", _) => {
+            return Err(String::from(
+                "No task is being tracked. Type /start followed by a task to start one.",
+            ));
+        }
+        _ => return Err(String::from("No task is paused.")),
+    }
+}
+
+fn hours(duration: Duration) -> f64 {
+    return duration.num_minutes() as f64 / 60.0;
+}
+
+pub fn print_report(intervals: &[Interval]) {
+    println!("Viewing tracked time:\n\n");
+
+    let mut totals: Vec<(&str, Duration)> = Vec::new();
+    for interval in intervals {
+        let duration = interval.end - interval.start;
+        println!(
+            "{} - {}\t{:.2}h\t{}",
+            timestamp(&interval.start),
+            timestamp(&interval.end),
+            hours(duration),
+            interval.task
+        );
+
+        match totals.iter_mut().find(|(task, _)| *task == interval.task) {
+            Some((_, total)) => *total = *total + duration,
+            None => totals.push((&interval.task, duration)),
+        }
+    }
+
+    println!("\n\nTotal per task:\n");
+    let mut all = Duration::zero();
+    for (task, total) in &totals {
+        println!("{:.2}h\t{}", hours(*total), task);
+        all = all + *total;
+    }
+    println!("\n{:.2}h\ttracked in total", hours(all));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn intervals_leave_out_pauses() {
+        let date: DateTime<FixedOffset> = Local.ymd(2022, 5, 21).and_hms(12, 0, 0).into();
+        let text = "Work - Saturday 2022/5/21\n\n\n09:00 am - [start] emails\n\t09:30 am - [pause] emails\n\t10:00 am - [resume] emails\n\t10:15 am - [stop] emails\n\n11:00 am - [start] coding";
+        let until: DateTime<FixedOffset> = Local.ymd(2022, 5, 21).and_hms(11, 45, 0).into();
+
+        let intervals = intervals(text, &date, until);
+        let minutes: Vec<(&str, i64)> = intervals
+            .iter()
+            .map(|interval| {
+                (
+                    interval.task.as_str(),
+                    (interval.end - interval.start).num_minutes(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            minutes,
+            vec![("emails", 30), ("emails", 15), ("coding", 45)]
+        );
+    }
+}
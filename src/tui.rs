@@ -0,0 +1,196 @@
+use crate::line_edit::{self, EditResult, LineEditor};
+use crate::{
+    complete_input, datestamp, display_journal_lines, handle_global_input, history_path, now,
+    timestamp, wait_for_input,
+};
+use chrono::NaiveDate;
+use console::{style, Key, Term};
+use std::ffi::OsStr;
+
+// The commands that can be run straight from the main screen without typing them out.
+const KEY_BINDINGS: [(char, &str, &str); 6] = [
+    ('\x10', "^P", "/prev"),
+    ('\x14', "^T", "/times"),
+    ('\x07', "^G", "/gtime"),
+    ('\x06', "^F", "/find"),
+    ('\x0f', "^O", "/switch"),
+    ('\x0e', "^N", "/new"),
+];
+
+// Ctrl+/ on most terminals
+const HELP_KEY: char = '\x1f';
+fn key_bindings_text() -> String {
+    let mut text = String::new();
+    for (_, label, command) in KEY_BINDINGS.iter() {
+        text.push_str(&format!("{} {}  ", label, &command[1..]));
+    }
+
+    text.push_str("^_ help  ^D exit");
+    return text;
+}
+
+fn fit_to_width(text: &str, width: usize) -> String {
+    let mut fitted: String = text.chars().take(width).collect();
+    let len = fitted.chars().count();
+    for _ in len..width {
+        fitted.push(' ');
+    }
+
+    return fitted;
+}
+
+// Draws the whole main screen. Everything is written into a buffered terminal and flushed
+// at once, and each row is overwritten in place rather than clearing the screen, so there is no flicker.
+// The journal's lines are only loaded again when the width of the terminal changes, so typing
+// doesn't read (and decrypt) the day on every key.
+fn draw(
+    term: &Term,
+    name: &OsStr,
+    message: &str,
+    streak: (usize, Option<NaiveDate>),
+    input: &LineEditor,
+    scroll: &mut usize,
+    journal_lines: &mut Option<(usize, Vec<String>)>,
+) {
+    let (height, width) = term.size();
+    let (height, width) = (height as usize, width as usize);
+
+    let prompt = format!("current->{}: ", name.to_string_lossy());
+    let mut rows_below_pane = 2;
+    if !message.is_empty() {
+        rows_below_pane += 1;
+    }
+
+    let pane_height = height.saturating_sub(rows_below_pane);
+
+    let lines = match journal_lines {
+        Some((lines_width, lines)) if *lines_width == width => lines,
+        _ => {
+            &journal_lines
+                .insert((width, display_journal_lines(name, width, streak)))
+                .1
+        }
+    };
+
+    let max_scroll = lines.len().saturating_sub(pane_height);
+    if *scroll > max_scroll {
+        *scroll = max_scroll;
+    }
+
+    // scroll is counted up from the bottom, so new entries are always in view by default
+    let end = lines.len() - *scroll;
+    let start = end.saturating_sub(pane_height);
+
+    let mut row = 0;
+    for line in &lines[start..end] {
+        let _ = term.move_cursor_to(0, row);
+        let _ = term.clear_line();
+        let _ = term.write_str(line);
+        row += 1;
+    }
+
+    while row < pane_height {
+        let _ = term.move_cursor_to(0, row);
+        let _ = term.clear_line();
+        row += 1;
+    }
+
+    if !message.is_empty() {
+        let _ = term.move_cursor_to(0, row);
+        let _ = term.clear_line();
+        let _ = term.write_str(&format!("{}", style(fit_to_width(message, width)).yellow()));
+        row += 1;
+    }
+
+    let date = now();
+    let scrolled = if *scroll > 0 {
+        format!(" [scrolled up {} lines]", *scroll)
+    } else {
+        String::from("")
+    };
+
+    let status = format!(
+        " {} | {} {}{} | {}",
+        name.to_string_lossy(),
+        datestamp(&date),
+        timestamp(&date),
+        scrolled,
+        key_bindings_text()
+    );
+
+    let _ = term.move_cursor_to(0, row);
+    let _ = term.clear_line();
+    let _ = term.write_str(&format!(
+        "{}",
+        style(fit_to_width(&status, width)).reverse()
+    ));
+    row += 1;
+
+    let _ = term.move_cursor_to(0, row);
+    input.render(term, &prompt);
+}
+
+// Shows the main screen and returns once the user has entered a line or used a keybinding.
+// Keybindings are returned as the command they stand for, so they are handled just like typed commands.
+// If nothing is pressed for idle_lock_minutes, the journal is locked.
+pub fn read_main_input(name: &OsStr, message: &str, streak: (usize, Option<NaiveDate>)) -> String {
+    let term = Term::buffered_stdout();
+    let history_path = history_path(name);
+    let history = match &history_path {
+        Some(path) => line_edit::load_history(path),
+        None => Vec::new(),
+    };
+    let mut input = LineEditor::new(history.clone(), |before_cursor| {
+        complete_input(name, before_cursor)
+    });
+    let mut scroll = 0;
+    let mut journal_lines = None;
+
+    let _ = term.clear_screen();
+    loop {
+        draw(
+            &term,
+            name,
+            message,
+            streak,
+            &input,
+            &mut scroll,
+            &mut journal_lines,
+        );
+
+        if !wait_for_input() {
+            return String::new();
+        }
+
+        let key = match term.read_key() {
+            Ok(key) => key,
+            // ctrl+c, or stdin went away
+            Err(_) => return handle_global_input(String::from("/exit")),
+        };
+
+        let page = (term.size().0 as usize).saturating_sub(3).max(1);
+
+        match input.handle_key(key) {
+            EditResult::Submit(text) => {
+                if let Some(path) = &history_path {
+                    line_edit::append_history(path, &history, &text);
+                }
+                return handle_global_input(text);
+            }
+            EditResult::Cancel => return handle_global_input(String::from("/exit")),
+            EditResult::Continue => {}
+            EditResult::Unhandled(key) => match key {
+                Key::PageUp => scroll += page,
+                Key::PageDown => scroll = scroll.saturating_sub(page),
+                Key::Char(HELP_KEY) => return handle_global_input(String::from("/help")),
+                Key::Char(c) => {
+                    if let Some((_, _, command)) = KEY_BINDINGS.iter().find(|(key, _, _)| *key == c)
+                    {
+                        return String::from(*command);
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+}